@@ -28,7 +28,7 @@ impl FloatExt for f32 {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 /// Tracks how many buttons are being pressed in specific directions
 pub struct InputState {
     /// Up keys down
@@ -39,6 +39,8 @@ pub struct InputState {
     pub left: u8,
     /// Right keys down
     pub right: u8,
+    /// Handbrake keys down
+    pub handbrake: u8,
 }
 
 impl InputState {
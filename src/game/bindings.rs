@@ -0,0 +1,323 @@
+//! Which keys and gamepad buttons do what
+//!
+//! A bindings file has a line per command listing everything bound to it:
+//!
+//! ```text
+//! <command> [binding...]
+//! ```
+//!
+//! Commands that aren't in the file keep their default bindings, and a command without bindings is unbound.
+//! The commands are:
+//!
+//! ```text
+//! throttle, brake, steer-left, steer-right, handbrake      held while driving
+//! shift-up, shift-down, neutral, gear-<n>, reverse         changing gear
+//! bias-front, bias-rear, wing-up, wing-down                adjusting the car
+//! camera, telemetry, graphs, hud, leaderboard              what is shown
+//! pause, bindings, quit
+//! ```
+//!
+//! Keys are bound by their lowercase name, like `w`, `up`, `space`, `left-bracket`, `kp1` or `f5`,
+//! and gamepad buttons by their name after `pad-`, like `pad-a` or `pad-right-shoulder`.
+//! `bindings` opens a screen for changing the bindings, which saves them to the file when it's closed.
+
+use ::*;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io::{self, Write, BufWriter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The bindings file used unless another one is given
+pub const BINDINGS_FILE: &str = "bindings.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Something the player can do by pressing a key or button
+pub enum Command {
+    Throttle,
+    Brake,
+    SteerLeft,
+    SteerRight,
+    Handbrake,
+    ShiftUp,
+    ShiftDown,
+    /// Put the car in a gear, `0` for neutral and `-1` for reverse
+    Gear(i8),
+    BiasFront,
+    BiasRear,
+    WingUp,
+    WingDown,
+    /// Cycle through the camera modes
+    Camera,
+    Telemetry,
+    Graphs,
+    /// Switch between the HUD layouts
+    Hud,
+    Leaderboard,
+    Pause,
+    /// Open the screen for changing the bindings
+    Bindings,
+    Quit,
+}
+
+impl Command {
+    /// Every command in the order they're listed in, with the gears the keyboard has keys for
+    pub fn all() -> Vec<Command> {
+        use self::Command::*;
+        let mut all = vec![Throttle, Brake, SteerLeft, SteerRight, Handbrake, ShiftUp, ShiftDown, Gear(0)];
+        all.extend((1..7).map(Gear));
+        all.extend(&[Gear(-1), BiasFront, BiasRear, WingUp, WingDown, Camera, Telemetry, Graphs, Hud, Leaderboard, Pause, Bindings, Quit]);
+        all
+    }
+    /// Whether the command lasts for as long as it's held
+    pub fn is_held(&self) -> bool {
+        use self::Command::*;
+        match *self {
+            Throttle | Brake | SteerLeft | SteerRight | Handbrake => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Command::*;
+        match *self {
+            Gear(0) => write!(f, "neutral"),
+            Gear(-1) => write!(f, "reverse"),
+            Gear(n) => write!(f, "gear-{}", n),
+            _ => f.write_str(match *self {
+                Throttle => "throttle",
+                Brake => "brake",
+                SteerLeft => "steer-left",
+                SteerRight => "steer-right",
+                Handbrake => "handbrake",
+                ShiftUp => "shift-up",
+                ShiftDown => "shift-down",
+                BiasFront => "bias-front",
+                BiasRear => "bias-rear",
+                WingUp => "wing-up",
+                WingDown => "wing-down",
+                Camera => "camera",
+                Telemetry => "telemetry",
+                Graphs => "graphs",
+                Hud => "hud",
+                Leaderboard => "leaderboard",
+                Pause => "pause",
+                Bindings => "bindings",
+                Quit => "quit",
+                Gear(_) => unreachable!(),
+            }),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let bad = || format!("Unknown command {}", s);
+        if s.starts_with("gear-") {
+            return match s["gear-".len()..].parse() {
+                Ok(n) if n > 0 => Ok(Command::Gear(n)),
+                _ => Err(bad()),
+            }
+        }
+        // Every other command is listed
+        Command::all().into_iter()
+            .find(|c| c.to_string() == s)
+            .ok_or_else(bad)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A key or gamepad button
+pub enum Binding {
+    Key(Keycode),
+    Pad(Button),
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Binding::Key(k) => match KEYS.iter().find(|&&(_, key)| key == k) {
+                Some(&(name, _)) => f.write_str(name),
+                None => write!(f, "{:?}", k),
+            },
+            Binding::Pad(b) => match BUTTONS.iter().find(|&&(_, btn)| btn == b) {
+                Some(&(name, _)) => write!(f, "pad-{}", name),
+                None => write!(f, "pad-{:?}", b),
+            },
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let found = if s.starts_with("pad-") {
+            BUTTONS.iter().find(|&&(name, _)| name == &s["pad-".len()..]).map(|&(_, b)| Binding::Pad(b))
+        } else {
+            KEYS.iter().find(|&&(name, _)| name == s).map(|&(_, k)| Binding::Key(k))
+        };
+        found.ok_or_else(|| format!("Unknown key or button {}", s))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// What every key and button is bound to, and the file they're saved in
+pub struct Bindings {
+    path: PathBuf,
+    binds: Vec<(Command, Binding)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use self::Command::*;
+        use self::Binding::{Key, Pad};
+        use Keycode::*;
+        let mut binds = vec![
+            (Throttle, Key(W)), (Throttle, Key(Up)),
+            (Brake, Key(S)), (Brake, Key(Down)),
+            (SteerLeft, Key(A)), (SteerLeft, Key(Left)),
+            (SteerRight, Key(D)), (SteerRight, Key(Right)),
+            (Handbrake, Key(Space)), (Handbrake, Pad(Button::X)),
+            (ShiftUp, Key(E)), (ShiftUp, Pad(Button::RightShoulder)),
+            (ShiftDown, Key(Q)), (ShiftDown, Pad(Button::LeftShoulder)),
+        ];
+        let gears = [(0, Num0, Kp0), (1, Num1, Kp1), (2, Num2, Kp2), (3, Num3, Kp3), (4, Num4, Kp4),
+            (5, Num5, Kp5), (6, Num6, Kp6), (-1, Num9, Kp9)];
+        for &(gear, num, kp) in &gears {
+            binds.push((Gear(gear), Key(num)));
+            binds.push((Gear(gear), Key(kp)));
+        }
+        binds.extend(&[
+            (BiasFront, Key(RightBracket)),
+            (BiasRear, Key(LeftBracket)),
+            (WingUp, Key(Period)),
+            (WingDown, Key(Comma)),
+            (Camera, Key(C)),
+            (Telemetry, Key(F5)),
+            (Graphs, Key(F6)),
+            (Hud, Key(F7)),
+            (Leaderboard, Key(F8)),
+            (Pause, Key(P)), (Pause, Pad(Button::Start)),
+            (Bindings, Key(F1)), (Bindings, Pad(Button::Back)),
+            (Quit, Key(Escape)),
+        ]);
+        self::Bindings {
+            path: PathBuf::from(BINDINGS_FILE),
+            binds,
+        }
+    }
+}
+
+impl Bindings {
+    /// Loads a bindings file on top of the default bindings, the file doesn't have to exist yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let mut bindings = Bindings {
+            path: path.as_ref().to_owned(),
+            .. Bindings::default()
+        };
+        if bindings.path.exists() {
+            let src = fs::read_to_string(&bindings.path).map_err(|e| e.to_string())?;
+            bindings.parse(&src)?;
+        }
+        Ok(bindings)
+    }
+    fn parse(&mut self, src: &str) -> Result<(), String> {
+        // The first line of a command replaces its default bindings
+        let mut seen = Vec::new();
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let mut words = line.split_whitespace();
+            let command: Command = words.next().unwrap().parse()?;
+            if !seen.contains(&command) {
+                seen.push(command);
+                self.clear(command);
+            }
+            for word in words {
+                self.binds.push((command, word.parse()?));
+            }
+        }
+        Ok(())
+    }
+    /// Saves the bindings to the file they were loaded from
+    pub fn save(&self) -> io::Result<()> {
+        let mut commands = Command::all();
+        for &(command, _) in &self.binds {
+            if !commands.contains(&command) {
+                commands.push(command);
+            }
+        }
+        let mut out = BufWriter::new(File::create(&self.path)?);
+        writeln!(out, "# car-driver bindings")?;
+        for command in commands {
+            write!(out, "{}", command)?;
+            for binding in self.bindings(command) {
+                write!(out, " {}", binding)?;
+            }
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+    /// Where the bindings are saved
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// The commands a key or button is bound to
+    pub fn commands(&self, binding: Binding) -> Vec<Command> {
+        self.binds.iter().filter(|&&(_, b)| b == binding).map(|&(c, _)| c).collect()
+    }
+    /// The keys and buttons bound to a command
+    pub fn bindings(&self, command: Command) -> Vec<Binding> {
+        self.binds.iter().filter(|&&(c, _)| c == command).map(|&(_, b)| b).collect()
+    }
+    /// Binds a key or button to a command and nothing else
+    pub fn bind(&mut self, command: Command, binding: Binding) {
+        self.binds.retain(|&(_, b)| b != binding);
+        self.binds.push((command, binding));
+    }
+    /// Unbinds everything from a command
+    pub fn clear(&mut self, command: Command) {
+        self.binds.retain(|&(c, _)| c != command);
+    }
+}
+
+/// The names of the keys that can be bound
+const KEYS: &[(&str, Keycode)] = &[
+    ("a", Keycode::A), ("b", Keycode::B), ("c", Keycode::C), ("d", Keycode::D), ("e", Keycode::E),
+    ("f", Keycode::F), ("g", Keycode::G), ("h", Keycode::H), ("i", Keycode::I), ("j", Keycode::J),
+    ("k", Keycode::K), ("l", Keycode::L), ("m", Keycode::M), ("n", Keycode::N), ("o", Keycode::O),
+    ("p", Keycode::P), ("q", Keycode::Q), ("r", Keycode::R), ("s", Keycode::S), ("t", Keycode::T),
+    ("u", Keycode::U), ("v", Keycode::V), ("w", Keycode::W), ("x", Keycode::X), ("y", Keycode::Y),
+    ("z", Keycode::Z),
+    ("0", Keycode::Num0), ("1", Keycode::Num1), ("2", Keycode::Num2), ("3", Keycode::Num3), ("4", Keycode::Num4),
+    ("5", Keycode::Num5), ("6", Keycode::Num6), ("7", Keycode::Num7), ("8", Keycode::Num8), ("9", Keycode::Num9),
+    ("kp0", Keycode::Kp0), ("kp1", Keycode::Kp1), ("kp2", Keycode::Kp2), ("kp3", Keycode::Kp3), ("kp4", Keycode::Kp4),
+    ("kp5", Keycode::Kp5), ("kp6", Keycode::Kp6), ("kp7", Keycode::Kp7), ("kp8", Keycode::Kp8), ("kp9", Keycode::Kp9),
+    ("kp-plus", Keycode::KpPlus), ("kp-minus", Keycode::KpMinus),
+    ("f1", Keycode::F1), ("f2", Keycode::F2), ("f3", Keycode::F3), ("f4", Keycode::F4), ("f5", Keycode::F5),
+    ("f6", Keycode::F6), ("f7", Keycode::F7), ("f8", Keycode::F8), ("f9", Keycode::F9), ("f10", Keycode::F10),
+    ("f11", Keycode::F11), ("f12", Keycode::F12),
+    ("up", Keycode::Up), ("down", Keycode::Down), ("left", Keycode::Left), ("right", Keycode::Right),
+    ("space", Keycode::Space), ("return", Keycode::Return), ("tab", Keycode::Tab),
+    ("backspace", Keycode::Backspace), ("escape", Keycode::Escape),
+    ("comma", Keycode::Comma), ("period", Keycode::Period), ("minus", Keycode::Minus), ("equals", Keycode::Equals),
+    ("left-bracket", Keycode::LeftBracket), ("right-bracket", Keycode::RightBracket),
+    ("semicolon", Keycode::Semicolon), ("quote", Keycode::Quote), ("slash", Keycode::Slash), ("backslash", Keycode::Backslash),
+    ("left-shift", Keycode::LShift), ("right-shift", Keycode::RShift), ("left-ctrl", Keycode::LCtrl), ("right-ctrl", Keycode::RCtrl),
+    ("insert", Keycode::Insert), ("delete", Keycode::Delete), ("home", Keycode::Home), ("end", Keycode::End),
+    ("page-up", Keycode::PageUp), ("page-down", Keycode::PageDown),
+];
+
+/// The names of the gamepad buttons
+const BUTTONS: &[(&str, Button)] = &[
+    ("a", Button::A), ("b", Button::B), ("x", Button::X), ("y", Button::Y),
+    ("back", Button::Back), ("guide", Button::Guide), ("start", Button::Start),
+    ("left-stick", Button::LeftStick), ("right-stick", Button::RightStick),
+    ("left-shoulder", Button::LeftShoulder), ("right-shoulder", Button::RightShoulder),
+    ("dpad-up", Button::DPadUp), ("dpad-down", Button::DPadDown), ("dpad-left", Button::DPadLeft), ("dpad-right", Button::DPadRight),
+];
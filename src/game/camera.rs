@@ -0,0 +1,141 @@
+use std::f32::consts::{PI, FRAC_PI_2};
+use std::str::FromStr;
+
+use ggez::nalgebra::Vector3;
+use ::*;
+
+/// How fast the camera catches up with its target, higher is stiffer
+const FOLLOW_RATE: f32 = 6.;
+/// How fast the camera turns with the car in chase mode
+const TURN_RATE: f32 = 4.;
+/// Seconds ahead of the car the look-ahead camera aims at
+const LOOK_AHEAD: f32 = 0.6;
+/// How far the look-ahead camera aims from the car at most, as a fraction of the screen
+const MAX_LOOK_AHEAD: f32 = 0.3;
+/// Zoom factor for one step of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What the camera does by itself
+pub enum CameraMode {
+    /// Stays where it is unless dragged with the mouse
+    Free,
+    /// Follows the car smoothly
+    Follow,
+    /// Follows the car and turns with it so it always points up
+    Chase,
+    /// Follows a point ahead of the car along its velocity
+    LookAhead,
+}
+
+impl Default for CameraMode {
+    fn default() -> Self {
+        CameraMode::Follow
+    }
+}
+
+impl CameraMode {
+    /// The mode after this one when cycling through them
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::Free => CameraMode::Follow,
+            CameraMode::Follow => CameraMode::Chase,
+            CameraMode::Chase => CameraMode::LookAhead,
+            CameraMode::LookAhead => CameraMode::Free,
+        }
+    }
+}
+
+impl FromStr for CameraMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "free" => Ok(CameraMode::Free),
+            "follow" => Ok(CameraMode::Follow),
+            "chase" => Ok(CameraMode::Chase),
+            "look-ahead" => Ok(CameraMode::LookAhead),
+            _ => Err(format!("Unknown camera mode {}, expected free, follow, chase or look-ahead", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Decides which part of the world is shown on the screen
+pub struct Camera {
+    pub mode: CameraMode,
+    /// The point in the world at the centre of the screen
+    pub pos: Point2,
+    /// How much the world is turned on the screen in radians
+    pub rot: f32,
+    /// How many screen pixels a world pixel takes up
+    pub zoom: f32,
+    /// Size of the screen in pixels
+    screen: Vector2,
+}
+
+impl Camera {
+    /// Makes a camera looking at `pos` on a screen of the given size
+    pub fn new(mode: CameraMode, pos: Point2, width: f32, height: f32) -> Self {
+        Camera {
+            mode,
+            pos,
+            rot: 0.,
+            zoom: 1.,
+            screen: Vector2::new(width, height),
+        }
+    }
+    /// Moves the camera towards where it wants to be with the target at `obj` moving at `velocity` m/s
+    pub fn update(&mut self, obj: &Object, velocity: Vector2, dt: f32) {
+        let mut target = obj.pos;
+        let mut target_rot = 0.;
+        match self.mode {
+            CameraMode::Free => return,
+            CameraMode::Follow => (),
+            CameraMode::Chase => target_rot = -obj.rot - FRAC_PI_2,
+            CameraMode::LookAhead => {
+                let mut ahead = LOOK_AHEAD * PIXELS_PER_METER * velocity;
+                let max = MAX_LOOK_AHEAD * self.screen.x.min(self.screen.y) / self.zoom;
+                if ahead.norm() > max {
+                    ahead *= max / ahead.norm();
+                }
+                target += ahead;
+            }
+        }
+
+        self.pos += (target - self.pos) * (1. - (-FOLLOW_RATE * dt).exp());
+        // Turn the shortest way round
+        let mut turn = (target_rot - self.rot) % (2. * PI);
+        if turn > PI {
+            turn -= 2. * PI;
+        } else if turn < -PI {
+            turn += 2. * PI;
+        }
+        self.rot += turn * (1. - (-TURN_RATE * dt).exp());
+    }
+    /// Zooms in by `steps` steps of the mouse wheel, or out if it is negative
+    pub fn zoom_by(&mut self, steps: i32) {
+        self.zoom = (self.zoom * ZOOM_STEP.powi(steps)).max(MIN_ZOOM).min(MAX_ZOOM);
+    }
+    /// Moves the camera so the world follows a movement on the screen
+    pub fn pan(&mut self, screen_delta: Vector2) {
+        let (sin, cos) = (-self.rot).sin_cos();
+        let d = Vector2::new(cos * screen_delta.x - sin * screen_delta.y, sin * screen_delta.x + cos * screen_delta.y);
+        self.pos -= d / self.zoom;
+    }
+    /// Cycles to the next mode
+    pub fn next_mode(&mut self) {
+        self.mode = self.mode.next();
+        if self.mode != CameraMode::Chase {
+            self.rot = 0.;
+        }
+    }
+    /// The transformation from world coordinates to the screen
+    pub fn transform(&self) -> Matrix4 {
+        Matrix4::new_translation(&(0.5 * self.screen).fixed_resize(0.))
+            * Matrix4::new_scaling(self.zoom)
+            * Matrix4::new_rotation(Vector3::new(0., 0., self.rot))
+            * Matrix4::new_translation(&(-self.pos.coords).fixed_resize(0.))
+    }
+}
@@ -0,0 +1,42 @@
+use ::*;
+use sim::replay::Replay;
+use sim::ai::AiDriver;
+
+/// What decides what a car does
+pub enum Controller {
+    /// The player at the keyboard or a gamepad
+    Player(KeyboardRamp),
+    /// A replay file played back from its first tick
+    Replay(Replay),
+    /// The AI following a racing line
+    Ai(AiDriver),
+}
+
+impl Controller {
+    /// What `car` does at `tick`, which lasts `dt` seconds,
+    /// given the keys the player holds, where their gamepads are and the actions they did
+    pub fn control(&mut self, tick: u64, dt: f32, car: &Car, keys: &InputState, pad: &Controls, actions: &[Action]) -> TickInput {
+        match *self {
+            Controller::Player(ref mut keyboard) => (keyboard.update(keys, dt).merge(*pad), actions.to_vec()),
+            // Keep going with nothing pressed once the replay is over
+            Controller::Replay(ref replay) => replay.get(tick)
+                .map(|(controls, actions)| (*controls, actions.to_vec()))
+                .unwrap_or_default(),
+            Controller::Ai(ref mut driver) => driver.drive(car),
+        }
+    }
+    /// Whether the player drives the car
+    pub fn is_player(&self) -> bool {
+        match *self {
+            Controller::Player(_) => true,
+            _ => false,
+        }
+    }
+    /// The replay being played back, if this is one
+    pub fn replay(&self) -> Option<&Replay> {
+        match *self {
+            Controller::Replay(ref replay) => Some(replay),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Gamepads, steering wheels and pedal sets
+//!
+//! Sticks, triggers, wheels and pedals all come in as controller axes. Which axis does what
+//! and how it responds is set in a gamepad file with one `key value...` line per setting:
+//!
+//! ```text
+//! steering <axis>               the axis that steers, left-x by default
+//! throttle <axis>               the axis that opens the throttle, trigger-right by default
+//! brake <axis>                  the axis that presses the brake, trigger-left by default
+//! clutch <axis>                 the axis that presses the clutch, none by default
+//! handbrake <axis>              the axis that pulls the handbrake, none by default
+//! invert <input>                turns an input's axis the other way around
+//! full-range <input>            for pedals that rest at one end of their axis instead of the middle
+//! dead-zone <input> <fraction>  how much of the axis next to where it rests does nothing
+//! curve <input> <exponent>      1 is linear, above 1 is finer near the rest and below 1 is coarser
+//! ```
+//!
+//! The inputs are `steering`, `throttle`, `brake`, `clutch` and `handbrake`. The axes are `left-x`, `left-y`,
+//! `right-x`, `right-y`, `trigger-left`, `trigger-right` or `none`.
+//! What the buttons do is set along with the keys in the bindings, see `game::bindings`.
+
+use ::*;
+
+#[derive(Debug, Clone, Copy)]
+/// How the position of an axis turns into a control
+pub struct AxisSetting {
+    /// The axis, if any
+    pub axis: Option<Axis>,
+    /// Whether the axis is turned the other way around
+    pub invert: bool,
+    /// Whether the axis rests at `-1` instead of `0`, like most pedals
+    pub full_range: bool,
+    /// Fraction of the axis next to the rest that does nothing
+    pub dead_zone: f32,
+    /// Exponent the position is raised to after the dead zone
+    pub curve: f32,
+}
+
+impl AxisSetting {
+    fn new(axis: Option<Axis>, dead_zone: f32) -> Self {
+        AxisSetting {
+            axis,
+            invert: false,
+            full_range: false,
+            dead_zone,
+            curve: 1.,
+        }
+    }
+    /// The control for an axis at `raw` from `-1` to `1`,
+    /// from `-1` to `1` if `signed` and from `0` to `1` otherwise
+    pub fn apply(&self, raw: f32, signed: bool) -> f32 {
+        let mut v = if self.invert { -raw } else { raw };
+        if self.full_range {
+            v = 0.5 * (v + 1.);
+        }
+        if !signed {
+            v = v.max(0.);
+        }
+        let past_dead_zone = ((v.abs() - self.dead_zone) / (1. - self.dead_zone)).max(0.).min(1.);
+        past_dead_zone.powf(self.curve) * v.signum()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Which axes of the gamepads do what
+pub struct PadSettings {
+    pub steering: AxisSetting,
+    pub throttle: AxisSetting,
+    pub brake: AxisSetting,
+    pub clutch: AxisSetting,
+    pub handbrake: AxisSetting,
+}
+
+impl Default for PadSettings {
+    fn default() -> Self {
+        PadSettings {
+            steering: AxisSetting::new(Some(Axis::LeftX), 0.1),
+            throttle: AxisSetting::new(Some(Axis::TriggerRight), 0.05),
+            brake: AxisSetting::new(Some(Axis::TriggerLeft), 0.05),
+            clutch: AxisSetting::new(None, 0.05),
+            handbrake: AxisSetting::new(None, 0.05),
+        }
+    }
+}
+
+impl PadSettings {
+    /// Reads the settings from the contents of a gamepad file, starting from the defaults
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut settings = PadSettings::default();
+
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line: {}", line);
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap();
+            let values: Vec<_> = words.collect();
+
+            match (key, &*values) {
+                ("invert", [i]) => settings.input(i).ok_or_else(bad)?.invert = true,
+                ("full-range", [i]) => {
+                    if *i == "steering" {
+                        return Err("The steering can't rest at the end of its axis".to_owned())
+                    }
+                    settings.input(i).ok_or_else(bad)?.full_range = true;
+                }
+                ("dead-zone", [i, f]) => {
+                    let f: f32 = f.parse().map_err(|_| bad())?;
+                    if !(f >= 0. && f < 1.) {
+                        return Err(format!("The dead zone has to be from 0 to less than 1: {}", line))
+                    }
+                    settings.input(i).ok_or_else(bad)?.dead_zone = f;
+                }
+                ("curve", [i, e]) => {
+                    let e: f32 = e.parse().map_err(|_| bad())?;
+                    if !(e > 0. && e.is_finite()) {
+                        return Err(format!("The curve has to be a positive number: {}", line))
+                    }
+                    settings.input(i).ok_or_else(bad)?.curve = e;
+                }
+                (i, [a]) => settings.input(i).ok_or_else(bad)?.axis = axis(a).ok_or_else(bad)?,
+                _ => return Err(bad()),
+            }
+        }
+        Ok(settings)
+    }
+    fn input(&mut self, name: &str) -> Option<&mut AxisSetting> {
+        match name {
+            "steering" => Some(&mut self.steering),
+            "throttle" => Some(&mut self.throttle),
+            "brake" => Some(&mut self.brake),
+            "clutch" => Some(&mut self.clutch),
+            "handbrake" => Some(&mut self.handbrake),
+            _ => None,
+        }
+    }
+}
+
+/// The axis with a name, `Some(None)` for `none`
+fn axis(name: &str) -> Option<Option<Axis>> {
+    Some(Some(match name {
+        "left-x" => Axis::LeftX,
+        "left-y" => Axis::LeftY,
+        "right-x" => Axis::RightX,
+        "right-y" => Axis::RightY,
+        "trigger-left" => Axis::TriggerLeft,
+        "trigger-right" => Axis::TriggerRight,
+        "none" => return Some(None),
+        _ => return None,
+    }))
+}
+
+fn axis_index(axis: Axis) -> usize {
+    match axis {
+        Axis::LeftX => 0,
+        Axis::LeftY => 1,
+        Axis::RightX => 2,
+        Axis::RightY => 3,
+        Axis::TriggerLeft => 4,
+        Axis::TriggerRight => 5,
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Where the axes of the gamepads are, all of them drive the player's car
+pub struct Gamepad {
+    settings: PadSettings,
+    /// Where each axis is from `-1` to `1`, `None` until it first moves
+    axes: [Option<f32>; 6],
+}
+
+impl Gamepad {
+    pub fn new(settings: PadSettings) -> Self {
+        Gamepad {
+            settings,
+            axes: [None; 6],
+        }
+    }
+    /// Moves an axis to where a controller event says it is
+    pub fn axis_moved(&mut self, axis: Axis, value: i16) {
+        self.axes[axis_index(axis)] = Some((value as f32 / i16::max_value() as f32).max(-1.));
+    }
+    /// Where the gamepads hold the controls
+    ///
+    /// Axes that haven't moved yet count as resting,
+    /// since pedals don't say where they are until they're pressed.
+    pub fn controls(&self) -> Controls {
+        let control = |setting: &AxisSetting, signed| setting.axis
+            .and_then(|axis| self.axes[axis_index(axis)])
+            .map(|raw| setting.apply(raw, signed))
+            .unwrap_or(0.);
+        Controls {
+            throttle: control(&self.settings.throttle, false),
+            brake: control(&self.settings.brake, false),
+            clutch: control(&self.settings.clutch, false),
+            handbrake: control(&self.settings.handbrake, false),
+            steering: control(&self.settings.steering, true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    /// The controls of a gamepad with some settings after moving an axis to `raw`
+    fn controls(src: &str, axis: Axis, raw: i16) -> Controls {
+        let mut pad = Gamepad::new(PadSettings::parse(src).unwrap());
+        pad.axis_moved(axis, raw);
+        pad.controls()
+    }
+
+    #[test]
+    fn resting_axes_do_nothing() {
+        let pad = Gamepad::new(PadSettings::default());
+        assert_eq!(pad.controls(), Controls::default());
+    }
+
+    #[test]
+    fn stick_steers_both_ways() {
+        assert!(close(controls("", Axis::LeftX, i16::max_value()).steering, 1.));
+        assert!(close(controls("", Axis::LeftX, i16::min_value()).steering, -1.));
+        assert!(close(controls("invert steering", Axis::LeftX, i16::max_value()).steering, -1.));
+    }
+
+    #[test]
+    fn dead_zone() {
+        // 0.05 of the way is inside the default 0.1 dead zone of the steering, 0.55 is halfway past it
+        assert_eq!(controls("", Axis::LeftX, 1638).steering, 0.);
+        assert!(close(controls("", Axis::LeftX, -18022).steering, -0.5));
+        assert!(close(controls("dead-zone steering 0", Axis::LeftX, 16384).steering, 0.5));
+
+        assert_eq!(controls("dead-zone throttle 0.5", Axis::TriggerRight, 16000).throttle, 0.);
+        assert!(close(controls("dead-zone throttle 0.5", Axis::TriggerRight, 24576).throttle, 0.5));
+    }
+
+    #[test]
+    fn curve() {
+        let src = "dead-zone steering 0\ncurve steering 2";
+        assert!(close(controls(src, Axis::LeftX, 16384).steering, 0.25));
+        assert!(close(controls(src, Axis::LeftX, -16384).steering, -0.25));
+
+        let src = "dead-zone brake 0\ncurve brake 0.5";
+        assert!(close(controls(src, Axis::TriggerLeft, 8192).brake, 0.5));
+    }
+
+    #[test]
+    fn pedals() {
+        // A trigger only counts pressed one way
+        assert_eq!(controls("", Axis::TriggerRight, -20000).throttle, 0.);
+
+        // A full-range pedal rests at -1 and is fully pressed at 1
+        let src = "dead-zone throttle 0\nfull-range throttle";
+        assert_eq!(controls(src, Axis::TriggerRight, i16::min_value()).throttle, 0.);
+        assert!(close(controls(src, Axis::TriggerRight, 0).throttle, 0.5));
+        assert!(close(controls(src, Axis::TriggerRight, i16::max_value()).throttle, 1.));
+
+        // Inverted it rests at 1 instead
+        let src = "dead-zone throttle 0\nfull-range throttle\ninvert throttle";
+        assert_eq!(controls(src, Axis::TriggerRight, i16::max_value()).throttle, 0.);
+        assert!(close(controls(src, Axis::TriggerRight, i16::min_value()).throttle, 1.));
+    }
+
+    #[test]
+    fn stick_as_pedals() {
+        let src = "throttle right-y\nbrake none\nclutch trigger-left\nfull-range clutch\ndead-zone clutch 0";
+        let c = controls(src, Axis::RightY, i16::max_value());
+        assert!(close(c.throttle, 1.));
+        assert_eq!(c.brake, 0.);
+        assert!(close(controls(src, Axis::TriggerLeft, 0).clutch, 0.5));
+        // The brake's old trigger is the clutch now
+        assert_eq!(controls(src, Axis::TriggerLeft, i16::max_value()).brake, 0.);
+    }
+
+    #[test]
+    fn bad_settings() {
+        assert!(PadSettings::parse("full-range steering").is_err());
+        assert!(PadSettings::parse("dead-zone throttle 1").is_err());
+        assert!(PadSettings::parse("dead-zone throttle 1.5").is_err());
+        assert!(PadSettings::parse("dead-zone throttle -0.1").is_err());
+        assert!(PadSettings::parse("curve brake 0").is_err());
+        assert!(PadSettings::parse("curve brake -1").is_err());
+        assert!(PadSettings::parse("dead-zone steering nan").is_err());
+        assert!(PadSettings::parse("curve throttle nan").is_err());
+        assert!(PadSettings::parse("curve throttle inf").is_err());
+        assert!(PadSettings::parse("steering left-z").is_err());
+        assert!(PadSettings::parse("wheel left-x").is_err());
+        assert!(PadSettings::parse("dead-zone steering 0.99\ncurve steering 3\nfull-range clutch").is_ok());
+    }
+}
@@ -1,7 +1,19 @@
 use ::*;
+use ggez::GameError;
 
 /// Stuff related to things in the world
 pub mod play;
+mod camera;
+mod controller;
+mod gamepad;
+mod bindings;
+mod remap;
+
+pub use self::camera::*;
+pub use self::controller::*;
+pub use self::gamepad::*;
+pub use self::bindings::*;
+pub use self::remap::*;
 
 use play::Play;
 
@@ -11,10 +23,7 @@ pub trait GameState {
     fn draw(&mut self, &State, &mut Context) -> GameResult<()>;
     fn draw_hud(&mut self, &State, &mut Context) -> GameResult<()>;
 
-    fn key_down(&mut self, &mut State, Keycode) {
-
-    }
-    fn key_up(&mut self, &mut State, Keycode) {
+    fn command(&mut self, &mut State, Command) {
 
     }
 }
@@ -22,28 +31,72 @@ pub trait GameState {
 pub struct Master {
     gs: Box<GameState>,
     state: State,
+    /// Time in seconds that hasn't been simulated yet
+    accumulator: f64,
+    /// What the keys and buttons do
+    bindings: Bindings,
+    /// The screen for changing the bindings, if it's open
+    remap: Option<Remap>,
+    paused: bool,
+    paused_text: PosText,
 }
 
 /// The state of the game
 pub struct State {
     mouse_down: MouseDown,
     input: InputState,
+    pad: Gamepad,
     assets: Assets,
     width: u32,
     height: u32,
     mouse: Point2,
-    offset: Vector2,
+    camera: Camera,
     switch_state: Option<Box<GameState>>,
+    timestep: Timestep,
+    /// How far the simulation is between the last tick and the next one from `0` to `1`
+    alpha: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How often and in how many steps the physics is simulated
+pub struct Timestep {
+    /// Physics ticks per second
+    pub tick_rate: u32,
+    /// Number of steps each tick is split into
+    pub substeps: u32,
+    /// How each step is integrated
+    pub integrator: Integrator,
+}
+
+impl Default for Timestep {
+    fn default() -> Self {
+        Timestep {
+            tick_rate: 60,
+            substeps: 1,
+            integrator: Integrator::default(),
+        }
+    }
 }
 
-const DESIRED_FPS: u32 = 60;
+impl Timestep {
+    /// Length of a tick in seconds
+    pub fn tick(&self) -> f32 {
+        1. / self.tick_rate as f32
+    }
+    /// Length of a single step in seconds
+    pub fn dt(&self) -> f32 {
+        self.tick() / self.substeps as f32
+    }
+}
+
+/// The most ticks that will be run in one frame to catch up if the simulation is too slow
+const MAX_CATCH_UP: u32 = 5;
 
-pub(crate) const DELTA: f32 = 1. / DESIRED_FPS as f32;
 pub const PIXELS_PER_METER: f32 = 15.;
 
 impl Master {
     /// Make a new state object
-    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+    pub fn new(ctx: &mut Context, opts: Options) -> GameResult<Self> {
         // Background colour is black
         graphics::set_background_color(ctx, (127, 127, 127, 255).into());
         // Initialise assets
@@ -53,32 +106,99 @@ impl Master {
         let width = ctx.conf.window_mode.width;
         let height = ctx.conf.window_mode.height;
 
+        let bindings = Bindings::load(&opts.bindings)
+            .map_err(|e| GameError::ConfigError(format!("Couldn't load the bindings {}: {}", opts.bindings, e)))?;
+
         Ok(Master {
-            gs: Box::new(Play::new(&assets, ctx)?),
+            gs: Box::new(Play::new(&assets, ctx, &opts)?),
+            accumulator: 0.,
+            bindings,
+            remap: None,
+            paused: false,
+            paused_text: assets.text_centred(ctx, Point2::new(width as f32 / 2., height as f32 / 3.), "Paused")?,
             state: State {
                 switch_state: None,
+                timestep: opts.timestep,
+                alpha: 0.,
                 input: Default::default(),
+                pad: Gamepad::new(opts.gamepad.clone()),
                 mouse_down: Default::default(),
                 assets,
                 width,
                 height,
                 mouse: Point2::new(0., 0.),
-                offset: Vector2::new(0., 0.),
+                // Starts out showing the top left corner of the world
+                camera: Camera::new(opts.camera, Point2::new(width as f32 / 2., height as f32 / 2.), width as f32, height as f32),
             },
         })
     }
 }
 
 impl State {
-    /// Sets the offset so that the given point will be centered on the screen
-    fn focus_on(&mut self, p: Point2) {
-        self.offset = -p.coords + 0.5 * Vector2::new(self.width as f32, self.height as f32);
-    }
     fn switch(&mut self, gs: Box<GameState>) {
         self.switch_state = Some(gs);
     }
 }
 
+impl Master {
+    /// Does what a key or button is bound to, or hands it to the bindings screen if that's open
+    fn press(&mut self, ctx: &mut Context, binding: Binding) {
+        if let Some(mut remap) = self.remap.take() {
+            if remap.press(binding, &mut self.bindings) {
+                self.remap = Some(remap);
+            } else {
+                // Keys let go of while the screen was open never got released
+                self.state.input = InputState::default();
+                if let Err(e) = self.bindings.save() {
+                    eprintln!("Couldn't save the bindings to {}: {}", self.bindings.path().display(), e);
+                }
+            }
+            return
+        }
+        for command in self.bindings.commands(binding) {
+            match command {
+                Command::Throttle => self.state.input.up += 1,
+                Command::Brake => self.state.input.down += 1,
+                Command::SteerLeft => self.state.input.left += 1,
+                Command::SteerRight => self.state.input.right += 1,
+                Command::Handbrake => self.state.input.handbrake += 1,
+                Command::Camera => self.state.camera.next_mode(),
+                Command::Pause => self.paused.toggle(),
+                Command::Bindings => {
+                    let (width, height) = (self.state.width as f32, self.state.height as f32);
+                    match Remap::new(&self.state.assets, ctx, width, height) {
+                        Ok(remap) => {
+                            self.remap = Some(remap);
+                            self.state.input = InputState::default();
+                        }
+                        Err(e) => eprintln!("Couldn't open the bindings: {}", e),
+                    }
+                }
+                Command::Quit => ctx.quit().unwrap(),
+                _ => (),
+            }
+            self.gs.command(&mut self.state, command)
+        }
+    }
+    /// Lets go of what a key or button is bound to
+    fn release(&mut self, binding: Binding) {
+        if self.remap.is_some() {
+            return
+        }
+        for command in self.bindings.commands(binding) {
+            let input = &mut self.state.input;
+            match command {
+                Command::Throttle => input.up = input.up.saturating_sub(1),
+                Command::Brake => input.down = input.down.saturating_sub(1),
+                Command::SteerLeft => input.left = input.left.saturating_sub(1),
+                Command::SteerRight => input.right = input.right.saturating_sub(1),
+                Command::Handbrake => input.handbrake = input.handbrake.saturating_sub(1),
+                _ => (),
+            }
+        }
+    }
+}
+
 use std::mem;
 
 impl EventHandler for Master {
@@ -89,12 +209,23 @@ impl EventHandler for Master {
             println!("Switched!");
         }
 
-        // Run this for every 1/60 of a second has passed since last update
-        // Can in theory become slow
-        while timer::check_update_time(ctx, DESIRED_FPS) {
-            self.gs.update(&mut self.state);
+        // Run a tick for every tick length that has passed since last update
+        // and remember how far we are into the next one for drawing
+        // Nothing moves while paused or changing the bindings
+        if !self.paused && self.remap.is_none() {
+            let tick = self.state.timestep.tick() as f64;
+            self.accumulator += timer::duration_to_f64(timer::get_delta(ctx));
+            self.accumulator = self.accumulator.min(MAX_CATCH_UP as f64 * tick);
+            while self.accumulator >= tick {
+                self.gs.update(&mut self.state);
+                self.accumulator -= tick;
+            }
+            self.state.alpha = (self.accumulator / tick) as f32;
         }
         self.gs.logic(&mut self.state, ctx);
+        if let Some(ref mut remap) = self.remap {
+            remap.update(&self.state.assets, ctx, &self.bindings)?;
+        }
 
         Ok(())
     }
@@ -104,17 +235,22 @@ impl EventHandler for Master {
         // Clear the screen first
         graphics::clear(ctx);
 
-        // Offset the current drawing with a translation from the `offset`
-        graphics::push_transform(ctx, Some(Matrix4::new_translation(&self.state.offset.fixed_resize(0.))));
+        // Draw the world as seen through the camera
+        graphics::push_transform(ctx, Some(self.state.camera.transform()));
         graphics::apply_transformations(ctx)?;
 
         self.gs.draw(&self.state, ctx)?;
 
-        // Pop the offset tranformation to draw the UI on the screen
+        // Pop the camera tranformation to draw the UI on the screen
         graphics::pop_transform(ctx);
         graphics::apply_transformations(ctx)?;
 
         self.gs.draw_hud(&self.state, ctx)?;
+        if let Some(ref remap) = self.remap {
+            remap.draw(ctx)?;
+        } else if self.paused {
+            self.paused_text.draw_text(ctx)?;
+        }
 
         // Flip the buffers to see what we just drew
         graphics::present(ctx);
@@ -130,17 +266,7 @@ impl EventHandler for Master {
             return
         }
 
-        use Keycode::*;
-        // Update input axes and quit game on Escape
-        match keycode {
-            W | Up => self.state.input.up += 1,
-            S | Down => self.state.input.down += 1,
-            A | Left => self.state.input.left += 1,
-            D | Right => self.state.input.right += 1,
-            Escape => ctx.quit().unwrap(),
-            _ => (),
-        }
-        self.gs.key_down(&mut self.state, keycode)
+        self.press(ctx, Binding::Key(keycode))
     }
     /// Handle key release events
     fn key_up_event(&mut self, _ctx: &mut Context, keycode: Keycode, _: Mod, repeat: bool) {
@@ -148,16 +274,19 @@ impl EventHandler for Master {
         if repeat {
             return
         }
-        use Keycode::*;
-
-        match keycode {
-            W | Up => self.state.input.up -= 1,
-            S | Down => self.state.input.down -= 1,
-            A | Left => self.state.input.left -= 1,
-            D | Right => self.state.input.right -= 1,
-            _ => (),
-        }
-        self.gs.key_up(&mut self.state, keycode)
+        self.release(Binding::Key(keycode))
+    }
+    /// Moves the axes of the gamepads
+    fn controller_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: i16, _instance_id: i32) {
+        self.state.pad.axis_moved(axis, value)
+    }
+    /// Handle gamepad button presses
+    fn controller_button_down_event(&mut self, ctx: &mut Context, btn: Button, _instance_id: i32) {
+        self.press(ctx, Binding::Pad(btn))
+    }
+    /// Handle gamepad button releases
+    fn controller_button_up_event(&mut self, _ctx: &mut Context, btn: Button, _instance_id: i32) {
+        self.release(Binding::Pad(btn))
     }
     /// Handle mouse down event
     fn mouse_button_down_event(&mut self, _ctx: &mut Context, btn: MouseButton, _x: i32, _y: i32) {
@@ -181,8 +310,17 @@ impl EventHandler for Master {
         }
     }
     /// Handles mouse movement events
-    fn mouse_motion_event(&mut self, _: &mut Context, _: MouseState, x: i32, y: i32, _: i32, _: i32) {
+    fn mouse_motion_event(&mut self, _: &mut Context, _: MouseState, x: i32, y: i32, xrel: i32, yrel: i32) {
         self.state.mouse = Point2::new(x as f32, y as f32);
+        // Dragging with the right or middle button moves the camera freely
+        if self.state.mouse_down.right || self.state.mouse_down.middle {
+            self.state.camera.mode = CameraMode::Free;
+            self.state.camera.pan(Vector2::new(xrel as f32, yrel as f32));
+        }
+    }
+    /// Zooms with the mouse wheel
+    fn mouse_wheel_event(&mut self, _: &mut Context, _x: i32, y: i32) {
+        self.state.camera.zoom_by(y);
     }
     fn quit_event(&mut self, _ctx: &mut Context) -> bool {
         false
@@ -1,26 +1,284 @@
 use ::*;
-use ggez::graphics::WHITE;
+use obj::setup::TyreSetup;
+use sim::replay::Recorder;
+use sim::telemetry::TelemetryLog;
+use sim::udp::UdpTelemetry;
+use sim::timing::{Gates, LapTimer, format_ticks};
+use sim::ghost::Ghost;
+use sim::leaderboard::{Leaderboard, TOP};
+use sim::ai::{AiDriver, RacingLine};
+use std::mem;
+use ggez::GameError;
+use ggez::graphics::{WHITE, DrawMode, Rect};
 use std::f32::consts::PI;
 
 /// The state of the game
 pub struct Play {
-    car: Car,
+    /// The simulation, where the first car is the one being driven or watched
+    sim: Sim<World>,
+    /// What drives each of the cars
+    controllers: Vec<Controller>,
+    /// Where the cars were at the previous tick, for interpolating when drawing
+    prev: Vec<Object>,
+    /// Actions to be done at the start of the next tick
+    actions: Vec<Action>,
+    recorder: Option<Recorder>,
+    /// The telemetry log and the file it's written to
+    telemetry: Option<(TelemetryLog, String)>,
+    udp: Option<UdpTelemetry>,
+    /// Lap timing, if the world has gates
+    timer: Option<LapTimer>,
+    world: WorldName,
+    /// Where the car has been at every tick of the current lap
+    lap_poses: Vec<Object>,
+    /// Id of the setup the car had when the current lap started
+    lap_setup: Option<u64>,
+    /// The lap being raced
+    ghost: Option<Ghost>,
+    /// Whether the ghost was given on the command line rather than being the best lap saved
+    fixed_ghost: bool,
+    /// Where valid runs are saved, if the file could be read
+    leaderboard: Option<Leaderboard>,
+    driver: String,
+    /// The setup and leaderboard place of the driver's last run
+    last_run: Option<(u64, usize)>,
+    board: Board,
+    show_board: bool,
     rev_meter: PosText,
     engine_performance: PosText,
     gear_text: PosText,
     steer_text: PosText,
+    brake_text: PosText,
+    aero_text: PosText,
+    tyre_texts: [PosText; 4],
+    status_text: PosText,
+    lap_text: PosText,
+    sector_text: PosText,
+    /// Speed, engine speed, pedals and lateral g over the last `GRAPH_SECONDS`
+    graphs: [Graph; 4],
+    show_graphs: bool,
+    layout: HudLayout,
+    cluster: Cluster,
+    /// Top speed of the car in m/s, worked out again when the wing is moved
+    top_speed: f32,
 }
 
+/// Colour of the car's shadow
+const SHADOW: Color = Color{r:0.,g:0.,b:0.,a:0.4};
+/// Offset of the shadow when the body is level
+const SHADOW_OFFSET: (f32, f32) = (3., 3.);
+/// Pixels the shadow moves per radian of roll or pitch
+const SHADOW_SCALE: f32 = 100.;
+/// Top left corner of the tyre widget measured from the top right of the screen
+const TYRE_WIDGET: (f32, f32) = (190., 4.);
+/// How many seconds back the graphs go
+const GRAPH_SECONDS: u32 = 10;
+/// Width and height of a graph
+const GRAPH_SIZE: (f32, f32) = (320., 70.);
+/// How far in m from an opponent's start a slot on the grid has to be to be free
+const GRID_CLEARANCE: f32 = 4.;
+
 impl Play {
-    pub fn new(a: &Assets, context: &mut Context) -> GameResult<Self> {
+    pub fn new(a: &Assets, context: &mut Context, opts: &Options) -> GameResult<Self> {
+        let (x, y) = (context.conf.window_mode.width as f32 - TYRE_WIDGET.0, TYRE_WIDGET.1);
+        let world = World::load(context, &opts.world)?;
+        let mut cars = vec![Car::new(600., 200.)];
+        let mut controllers = vec![Controller::Player(KeyboardRamp::default())];
+        // The opponents start where they were recorded
+        for replay in &opts.opponents {
+            let mut car = Car::new(0., 0.);
+            car.obj = replay.start;
+            cars.push(car);
+            controllers.push(Controller::Replay(replay.clone()));
+        }
+        // The AI follows the line it's given, the track's own or else the track's centreline
+        let line = match (&opts.racing_line, &opts.world, world.track()) {
+            _ if !opts.ai && opts.ai_opponents == 0 => None,
+            (&Some(ref line), _, _) => Some(line.clone()),
+            (&None, &WorldName::Track(ref name), Some(track)) => Some(match RacingLine::load(context, name)? {
+                Some(line) => line,
+                None => RacingLine::centreline(track),
+            }),
+            _ => None,
+        };
+        let ai = || line.clone()
+            .map(|line| Controller::Ai(AiDriver::new(line)))
+            .ok_or_else(|| GameError::ConfigError("The AI has no racing line to follow".to_owned()));
+        if let Some(ref replay) = opts.replay {
+            cars[0].obj = replay.start;
+            controllers[0] = Controller::Replay(replay.clone());
+        } else if opts.ai {
+            controllers[0] = ai()?;
+        }
+        if let Some(track) = world.track() {
+            // Take the first slots on the grid no opponent is on, the driver first and then the AI
+            let free = |slot: &Object| opts.opponents.iter().all(|r| (r.start.pos - slot.pos).norm() > GRID_CLEARANCE * PIXELS_PER_METER);
+            let mut slots = (0..track.grid_slots()).map(|i| track.grid(i)).filter(free);
+            if opts.replay.is_none() {
+                cars[0].obj = slots.next().unwrap_or_else(|| track.grid(0));
+            }
+            for _ in 0..opts.ai_opponents {
+                let mut car = Car::new(0., 0.);
+                car.obj = slots.next()
+                    .ok_or_else(|| GameError::ConfigError(format!("The grid of {} doesn't have room for all the cars", track.name)))?;
+                cars.push(car);
+                controllers.push(ai()?);
+            }
+        }
+        let sim = Sim::new(cars, world, opts.timestep, opts.seed);
+        let gates = match opts.world {
+            WorldName::Track(ref name) => Gates::load(context, name)?,
+            WorldName::SurfaceMap => None,
+        };
+        let timer = match (gates, opts.start_line) {
+            (Some(gates), Some(start)) => Some(Gates { start, .. gates }),
+            (None, Some(start)) => Some(Gates { start, checkpoints: Vec::new(), finish: None }),
+            (gates, None) => gates,
+        }.map(|gates| LapTimer::new(Gates { finish: opts.finish_line.or(gates.finish), .. gates }));
+        let ghost = match opts.ghost {
+            Some(ref ghost) => Some(ghost.clone()),
+            None => Ghost::load(Ghost::path(&opts.world, sim.cars[0].setup.id())).ok(),
+        };
+        let recorder = match opts.record {
+            Some(ref path) => Some(Recorder::create(path, &sim, &opts.world, opts.seed, sim.cars[0].obj)?),
+            None => None,
+        };
+        let telemetry = match opts.telemetry {
+            Some(ref path) => Some((TelemetryLog::create(path)?, path.clone())),
+            None => None,
+        };
+        let udp = match opts.udp {
+            Some(addr) => Some(UdpTelemetry::new(addr)?),
+            None => None,
+        };
+        let leaderboard = match Leaderboard::load(&opts.leaderboard) {
+            Ok(leaderboard) => Some(leaderboard),
+            Err(e) => {
+                eprintln!("Couldn't load the leaderboard {}, runs won't be saved: {}", opts.leaderboard, e);
+                None
+            }
+        };
+
+        let samples = (GRAPH_SECONDS * opts.timestep.tick_rate) as usize;
+        let bottom = context.conf.window_mode.height as f32 - 4.;
+        let graph_rect = |i: usize| Rect::new(4., bottom - (4 - i) as f32 * (GRAPH_SIZE.1 + 4.), GRAPH_SIZE.0, GRAPH_SIZE.1);
+        let (top_speed, redline) = (sim.cars[0].setup.top_speed(), sim.cars[0].setup.engine.redline_rpm);
+        let graphs = [
+            Graph::new(a, context, graph_rect(0), "Speed km/h", 0., 1.1 * top_speed * 3.6, &[WHITE], samples)?,
+            Graph::new(a, context, graph_rect(1), "Engine RPM", 0., 1.1 * redline, &[YELLOW], samples)?,
+            Graph::new(a, context, graph_rect(2), "Throttle/brake", 0., 1., &[LIME, SCARLET], samples)?,
+            Graph::new(a, context, graph_rect(3), "Lateral g", -3., 3., &[CYAN], samples)?,
+        ];
+
+        let (width, height) = (context.conf.window_mode.width as f32, context.conf.window_mode.height as f32);
+        let cluster = Cluster::new(a, context, &sim.cars[0].setup, top_speed, width, height)?;
+        let board = Board::new(a, context, width, height)?;
+
         Ok(Play {
-            car: Car::new(100., 50.),
+            prev: sim.cars.iter().map(|car| car.obj).collect(),
+            sim,
+            controllers,
+            actions: Vec::new(),
+            recorder,
+            telemetry,
+            udp,
+            timer,
+            world: opts.world.clone(),
+            lap_poses: Vec::new(),
+            lap_setup: None,
+            ghost,
+            fixed_ghost: opts.ghost.is_some(),
+            leaderboard,
+            driver: opts.driver.clone(),
+            last_run: None,
+            board,
+            show_board: false,
             rev_meter: a.text(context, Point2::new(2., 2.), "Revs: 0000 RPM")?,
             engine_performance: a.text(context, Point2::new(2., 18.), "Torque|Power: 200 N m | 100 kW")?,
             gear_text: a.text(context, Point2::new(2., 34.), "Gear: 1")?,
             steer_text: a.text(context, Point2::new(2., 50.), "Steer:  0°")?,
+            brake_text: a.text(context, Point2::new(2., 66.), "Brakes: F  20°C R  20°C | Bias: 60/40")?,
+            aero_text: a.text(context, Point2::new(2., 82.), "Wing:  8° | Top speed: 000 km/h | Grip at 150 km/h: 0.00 g")?,
+            tyre_texts: [
+                a.text(context, Point2::new(x + 16., y + 4.), " 20° 0%")?,
+                a.text(context, Point2::new(x + 106., y + 4.), " 20° 0%")?,
+                a.text(context, Point2::new(x + 16., y + 38.), " 20° 0%")?,
+                a.text(context, Point2::new(x + 106., y + 38.), " 20° 0%")?,
+            ],
+            status_text: a.text(context, Point2::new(2., 98.), " ")?,
+            lap_text: a.text(context, Point2::new(2., 114.), " ")?,
+            sector_text: a.text(context, Point2::new(2., 130.), " ")?,
+            graphs,
+            show_graphs: false,
+            layout: opts.hud,
+            cluster,
+            top_speed,
         })
     }
+    /// Stops logging telemetry or starts logging to a new file named after the current tick
+    fn toggle_telemetry(&mut self) {
+        if self.telemetry.take().is_some() {
+            return
+        }
+        let path = format!("telemetry-{}.csv", self.sim.tick);
+        match TelemetryLog::create(&path) {
+            Ok(log) => self.telemetry = Some((log, path)),
+            Err(e) => eprintln!("Couldn't create {}: {}", path, e),
+        }
+    }
+    /// Saves the lap just finished as the ghost if it's the best yet with the current setup,
+    /// unless the player didn't drive it or changed the setup along the way
+    fn finish_ghost(&mut self) {
+        let setup = self.sim.cars[0].setup.id();
+        if !self.controllers[0].is_player() || self.lap_setup != Some(setup) {
+            return
+        }
+        let ghost = Ghost {
+            world: self.world.clone(),
+            setup,
+            tick_rate: self.sim.timestep.tick_rate,
+            poses: mem::replace(&mut self.lap_poses, Vec::new()),
+        };
+        let path = Ghost::path(&ghost.world, ghost.setup);
+        if let Ok(best) = Ghost::load(&path) {
+            if best.lap_time() <= ghost.lap_time() {
+                return
+            }
+        }
+        if let Err(e) = ghost.save(&path) {
+            eprintln!("Couldn't save the ghost to {}: {}", path.display(), e);
+        }
+        if !self.fixed_ghost {
+            self.ghost = Some(ghost);
+        }
+    }
+    /// Changes `by` gears from the gear already asked for this tick, so quick presses add up
+    fn shift(&self, by: i8) -> Action {
+        let car = &self.sim.cars[0];
+        let gear = self.actions.iter().rev()
+            .filter_map(|a| match *a {
+                Action::Gear(gear) => Some(gear),
+                _ => None,
+            })
+            .next()
+            .unwrap_or(car.gear);
+        Action::Gear((gear + by).max(-1).min(car.setup.transmission.gears()))
+    }
+    /// Puts a run that was just finished on the leaderboard,
+    /// unless the player didn't drive it or changed the setup along the way
+    fn add_run(&mut self, ticks: u64) {
+        let setup = self.sim.cars[0].setup.id();
+        if !self.controllers[0].is_player() || self.lap_setup != Some(setup) {
+            return
+        }
+        let millis = ticks * 1000 / self.sim.timestep.tick_rate as u64;
+        if let Some(ref mut leaderboard) = self.leaderboard {
+            match leaderboard.add(&self.world, setup, millis, &self.driver) {
+                Ok(place) => self.last_run = Some((setup, place)),
+                Err(e) => eprintln!("Couldn't save the run to the leaderboard: {}", e),
+            }
+        }
+    }
 }
 
 /*
@@ -29,48 +287,314 @@ impl Play {
 */
 impl GameState for Play {
     fn update(&mut self, s: &mut State) {
-        self.car.update(&s.input);
+        for (prev, car) in self.prev.iter_mut().zip(&self.sim.cars) {
+            *prev = car.obj;
+        }
+        let actions = mem::replace(&mut self.actions, Vec::new());
+        let (tick, dt) = (self.sim.tick, self.sim.timestep.tick());
+        let pad = s.pad.controls();
+        let input: Vec<_> = self.controllers.iter_mut()
+            .zip(&self.sim.cars)
+            .map(|(controller, car)| controller.control(tick, dt, car, &s.input, &pad, &actions))
+            .collect();
+
+        // Only the player's driving is recorded
+        if self.controllers[0].replay().is_none() {
+            let failed = match self.recorder {
+                Some(ref mut recorder) => recorder.record(&input[0].0, &input[0].1).err(),
+                None => None,
+            };
+            if let Some(e) = failed {
+                eprintln!("Stopped recording: {}", e);
+                self.recorder = None;
+            }
+        }
+        self.sim.tick(&input);
+        if input[0].1.iter().any(|a| match *a { Action::AdjustWing(_) => true, _ => false }) {
+            self.top_speed = self.sim.cars[0].setup.top_speed();
+        }
+
+        let failed = match self.telemetry {
+            Some((ref mut log, _)) => log.log(&self.sim, 0).err(),
+            None => None,
+        };
+        if let Some(e) = failed {
+            eprintln!("Stopped logging telemetry: {}", e);
+            self.telemetry = None;
+        }
+        if let Some(ref udp) = self.udp {
+            udp.send(&self.sim, 0);
+        }
+
+        let (finished, lap_ticks) = match self.timer {
+            Some(ref mut timer) => {
+                let finished = timer.update(self.prev[0].pos, self.sim.cars[0].obj.pos, self.sim.tick, self.sim.ground.track())
+                    .map(|lap| (lap.ticks, lap.valid));
+                (finished, timer.current(self.sim.tick))
+            }
+            None => (None, None),
+        };
+        if let Some((ticks, valid)) = finished {
+            self.lap_poses.push(self.sim.cars[0].obj);
+            if valid {
+                self.finish_ghost();
+                self.add_run(ticks);
+            }
+            self.lap_poses.clear();
+        }
+        match lap_ticks {
+            // A new lap has started
+            Some(0) => {
+                self.lap_poses.push(self.sim.cars[0].obj);
+                // Race the best lap with the setup the car has now
+                let setup = self.sim.cars[0].setup.id();
+                self.lap_setup = Some(setup);
+                if !self.fixed_ghost && self.ghost.as_ref().map(|g| g.setup) != Some(setup) {
+                    self.ghost = Ghost::load(Ghost::path(&self.world, setup)).ok();
+                }
+            }
+            Some(_) => self.lap_poses.push(self.sim.cars[0].obj),
+            None => (),
+        }
+
+        // The graphs keep going while hidden so they're full when shown
+        let car = &self.sim.cars[0];
+        self.graphs[0].push(&[car.velocity.norm() * 3.6]);
+        self.graphs[1].push(&[car.engine_speed]);
+        self.graphs[2].push(&[car.throttle, car.brake]);
+        self.graphs[3].push(&[car.g_forces().0]);
     }
-    fn key_down(&mut self, _s: &mut State, k: Keycode) {
-        match k {
-            Keycode::Kp0 | Keycode::Num0 => self.car.gear = 0,
-            Keycode::Kp1 | Keycode::Num1 => self.car.gear = 1,
-            Keycode::Kp2 | Keycode::Num2 => self.car.gear = 2,
-            Keycode::Kp3 | Keycode::Num3 => self.car.gear = 3,
-            Keycode::Kp4 | Keycode::Num4 => self.car.gear = 4,
-            Keycode::Kp5 | Keycode::Num5 => self.car.gear = 5,
-            Keycode::Kp6 | Keycode::Num6 => self.car.gear = 6,
-            Keycode::Kp9 | Keycode::Num9 => self.car.gear = -1,
+    fn command(&mut self, _s: &mut State, command: Command) {
+        match command {
+            Command::Telemetry => {
+                self.toggle_telemetry();
+                return
+            }
+            Command::Graphs => {
+                self.show_graphs = !self.show_graphs;
+                return
+            }
+            Command::Hud => {
+                self.layout = self.layout.toggle();
+                return
+            }
+            Command::Leaderboard => {
+                self.show_board = !self.show_board;
+                return
+            }
             _ => (),
         }
+        // The replay or the AI does all the driving
+        if !self.controllers[0].is_player() {
+            return
+        }
+        let action = match command {
+            Command::Gear(gear) => {
+                // The bindings can have gears the car doesn't
+                let transmission = &self.sim.cars[0].setup.transmission;
+                Action::Gear(gear.max(-transmission.reverse_gears()).min(transmission.gears()))
+            }
+            Command::ShiftUp => self.shift(1),
+            Command::ShiftDown => self.shift(-1),
+            Command::BiasRear => Action::AdjustBias(-0.01),
+            Command::BiasFront => Action::AdjustBias(0.01),
+            Command::WingDown => Action::AdjustWing(-1.),
+            Command::WingUp => Action::AdjustWing(1.),
+            _ => return,
+        };
+        self.actions.push(action);
     }
     fn logic(&mut self, s: &mut State, ctx: &mut Context) {
-        // Center the camera on the player
-        // let p = self.car.obj.pos;
-        // s.focus_on(p);
-        let ang = angle_to_vec(self.car.obj.rot);
-        let speed_forwards = self.car.velocity.dot(&ang);
-        let slip_speed = self.car.velocity.perp(&ang);
-        let rpm = self.car.engine_speed;
-        let (torque, power) = self.car.setup.engine.and_power(rpm);
+        // Move the camera along with where the car is drawn
+        let obj = self.prev[0].lerp(&self.sim.cars[0].obj, s.alpha);
+        let dt = timer::duration_to_f64(timer::get_delta(ctx)) as f32;
+        s.camera.update(&obj, self.sim.cars[0].velocity, dt);
+
+        let ang = angle_to_vec(self.sim.cars[0].obj.rot);
+        let speed_forwards = self.sim.cars[0].velocity.dot(&ang);
+        let slip_speed = self.sim.cars[0].velocity.perp(&ang);
+        let rpm = self.sim.cars[0].engine_speed;
+        let (torque, power) = self.sim.cars[0].setup.engine.and_power(rpm);
 
         self.rev_meter.update_text(&s.assets, ctx, &format!("Revs: {:04.0} RPM  Speed: {:4.0} km/h | ({:2.0} km/h)", rpm, speed_forwards*3.6, slip_speed*3.6)).unwrap();
         self.engine_performance.update_text(&s.assets, ctx, &format!("Torque|Power: {:03.0} N m | {:3.0} hp", torque, power)).unwrap();
         self.gear_text.update_text(&s.assets, ctx, &format!("Gear: {}  |  C: {:4.2} B: {:4.2} T: {:4.2}",
-            self.car.setup.transmission.display(self.car.gear), self.car.clutch, self.car.brake, self.car.throttle)).unwrap();
-        self.steer_text.update_text(&s.assets, ctx, &format!("Steer: {:2.0}°", self.car.steering_angle*180./PI)).unwrap();
+            self.sim.cars[0].setup.transmission.display(self.sim.cars[0].gear), self.sim.cars[0].clutch, self.sim.cars[0].brake, self.sim.cars[0].throttle)).unwrap();
+        self.steer_text.update_text(&s.assets, ctx, &format!("Steer: {:2.0}°  |  Surface: {:?}  |  Roll: {:4.1}° Pitch: {:4.1}°",
+            self.sim.cars[0].steering_angle*180./PI, self.sim.ground.get(self.sim.cars[0].obj.pos),
+            self.sim.cars[0].suspension.roll*180./PI, self.sim.cars[0].suspension.pitch*180./PI)).unwrap();
+        let bias = self.sim.cars[0].setup.brakes.bias * 100.;
+        self.brake_text.update_text(&s.assets, ctx, &format!("Brakes: F {:3.0}°C R {:3.0}°C | Bias: {:2.0}/{:2.0}",
+            self.sim.cars[0].brake_temp[0], self.sim.cars[0].brake_temp[1], bias, 100. - bias)).unwrap();
+        let setup = &self.sim.cars[0].setup;
+        self.aero_text.update_text(&s.assets, ctx, &format!("Wing: {:2.0}° | Top speed: {:3.0} km/h | Grip at 150 km/h: {:4.2} g",
+            setup.aero.wing_angle, self.top_speed*3.6, setup.cornering_grip(150./3.6))).unwrap();
+        let mut status = match (self.controllers[0].replay(), &self.recorder) {
+            (Some(replay), _) => format!("Replay: tick {}/{}", self.sim.tick.min(replay.len()), replay.len()),
+            (_, &Some(_)) => format!("Recording: tick {}", self.sim.tick),
+            _ if !self.controllers[0].is_player() => "AI driving".to_owned(),
+            _ => " ".to_owned(),
+        };
+        if let Some((_, ref path)) = self.telemetry {
+            status.push_str(&format!("  Telemetry: {}", path));
+        }
+        if let Some(track) = self.sim.ground.track() {
+            let pos = self.sim.cars[0].obj.pos;
+            status.push_str(&format!("  {}: {:4.0}/{:.0} m{}", track.name, track.distance(pos), track.length(),
+                if track.is_on_track(pos) { "" } else { " (off track)" }));
+        }
+        self.status_text.update_text(&s.assets, ctx, &status).unwrap();
+        if let Some(ref timer) = self.timer {
+            let (mut lap, sectors) = timing_texts(timer, self.sim.tick, self.sim.timestep.tick_rate);
+            if let Some(ref ghost) = self.ghost {
+                lap.push_str(&format!("  Ghost: {}", format_ticks(ghost.lap_ticks(), ghost.tick_rate)));
+            }
+            self.lap_text.update_text(&s.assets, ctx, &lap).unwrap();
+            self.sector_text.update_text(&s.assets, ctx, &sectors).unwrap();
+        }
+        for (text, tyre) in self.tyre_texts.iter_mut().zip(self.sim.cars[0].tyres.iter()) {
+            text.update_text(&s.assets, ctx, &format!("{:3.0}° {:.0}%", tyre.surface_temp, tyre.wear * 100.)).unwrap();
+        }
+        if self.layout == HudLayout::Gauges {
+            self.cluster.update(&s.assets, ctx, &self.sim.cars[0], self.top_speed).unwrap();
+        }
+        if self.show_board {
+            let setup = self.sim.cars[0].setup.id();
+            let title = format!("Top {} in {} with setup {:016x}", TOP, self.world.file_name(), setup);
+            let top = self.leaderboard.as_ref().map(|l| l.top(&self.world, setup)).unwrap_or_default();
+            let highlight = match self.last_run {
+                Some((run_setup, place)) if run_setup == setup => Some(place - 1),
+                _ => None,
+            };
+            self.board.update(&s.assets, ctx, &title, &top, highlight).unwrap();
+        }
     }
 
     fn draw(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
         graphics::set_color(ctx, WHITE)?;
-        self.car.obj.draw(ctx, s.assets.get_img(Sprite::Ferrari))?;
+        self.sim.ground.draw(ctx)?;
+
+        // The ghost is as far into its lap as the car is drawn into the current one
+        let tick_rate = self.sim.timestep.tick_rate as f32;
+        let lap_time = self.timer.as_ref()
+            .and_then(|timer| timer.current(self.sim.tick))
+            .map(|ticks| (ticks as f32 - 1. + s.alpha).max(0.) / tick_rate);
+        if let (Some(ghost), Some(time)) = (self.ghost.as_ref(), lap_time) {
+            if let Some(pose) = ghost.pose(time) {
+                graphics::set_color(ctx, TRANS)?;
+                pose.draw(ctx, s.assets.get_img(Sprite::Ferrari))?;
+            }
+        }
+
+        // The first car is drawn last so it's on top
+        for (prev, car) in self.prev.iter().zip(&self.sim.cars).rev() {
+            let obj = prev.lerp(&car.obj, s.alpha);
+
+            // The shadow moves with the body as it rolls and pitches on the springs
+            let forwards = angle_to_vec(obj.rot);
+            let right = Vector2::new(-forwards.y, forwards.x);
+            let body = car.suspension.roll * right - car.suspension.pitch * forwards;
+            let shadow = Object {
+                pos: obj.pos + Vector2::new(SHADOW_OFFSET.0, SHADOW_OFFSET.1) + SHADOW_SCALE * body,
+                rot: obj.rot,
+            };
+            graphics::set_color(ctx, SHADOW)?;
+            let sprite = s.assets.get_img(car.sprite);
+            shadow.draw(ctx, sprite)?;
+
+            graphics::set_color(ctx, WHITE)?;
+            obj.draw(ctx, sprite)?;
+        }
 
         Ok(())
     }
-    fn draw_hud(&mut self, _s: &State, ctx: &mut Context) -> GameResult<()> {
-        self.rev_meter.draw_text(ctx)?;
-        self.engine_performance.draw_text(ctx)?;
-        self.gear_text.draw_text(ctx)?;
-        self.steer_text.draw_text(ctx)
+    fn draw_hud(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        // Draw the tyres as seen from above coloured by their temperature
+        let (x, y) = (s.width as f32 - TYRE_WIDGET.0, TYRE_WIDGET.1);
+        for (i, tyre) in self.sim.cars[0].tyres.iter().enumerate() {
+            let (tx, ty) = (x + 90. * (i % 2) as f32, y + 34. * (i / 2) as f32);
+            graphics::set_color(ctx, tyre_colour(tyre, &self.sim.cars[0].setup.tyres))?;
+            graphics::rectangle(ctx, DrawMode::Fill, Rect::new(tx, ty, 12., 24.))?;
+        }
+        graphics::set_color(ctx, WHITE)?;
+        for text in &self.tyre_texts {
+            text.draw_text(ctx)?;
+        }
+
+        match self.layout {
+            HudLayout::Text => {
+                self.rev_meter.draw_text(ctx)?;
+                self.engine_performance.draw_text(ctx)?;
+                self.gear_text.draw_text(ctx)?;
+            }
+            HudLayout::Gauges => {
+                self.cluster.draw(ctx, &self.sim.cars[0])?;
+                graphics::set_color(ctx, WHITE)?;
+            }
+        }
+        self.steer_text.draw_text(ctx)?;
+        self.brake_text.draw_text(ctx)?;
+        self.aero_text.draw_text(ctx)?;
+        self.status_text.draw_text(ctx)?;
+        if self.timer.is_some() {
+            self.lap_text.draw_text(ctx)?;
+            self.sector_text.draw_text(ctx)?;
+        }
+
+        if self.show_graphs {
+            for graph in &self.graphs {
+                graph.draw(ctx)?;
+            }
+        }
+        if self.show_board {
+            self.board.draw(ctx)?;
+        }
+        Ok(())
+    }
+}
+
+/// The lines about the current lap and its sectors
+fn timing_texts(timer: &LapTimer, tick: u64, tick_rate: u32) -> (String, String) {
+    let time = |ticks: Option<u64>| ticks.map(|t| format_ticks(t, tick_rate)).unwrap_or_else(|| "-:--.---".to_owned());
+    let seconds = |ticks: i64| ticks as f32 / tick_rate as f32;
+
+    let mut lap = format!("Lap {}: {}{}  Last: {}{}  Best: {}",
+        timer.lap_number(), time(timer.current(tick)), if timer.current_valid() { "" } else { " (invalid)" },
+        time(timer.last().map(|l| l.ticks)), if timer.last().map(|l| l.valid).unwrap_or(true) { "" } else { " (invalid)" },
+        time(timer.best().map(|l| l.ticks)));
+    if let Some(delta) = timer.delta(tick) {
+        lap.push_str(&format!("  Delta: {:+.2}", seconds(delta)));
+    }
+
+    let mut sectors = String::new();
+    let mut last = 0;
+    for (i, &split) in timer.splits().iter().enumerate() {
+        let sector = split - last;
+        last = split;
+        sectors.push_str(&format!("S{}: {:.3}", i + 1, seconds(sector as i64)));
+        if let Some(best) = timer.best() {
+            sectors.push_str(&format!(" ({:+.3})", seconds(sector as i64 - best.sectors[i] as i64)));
+        }
+        sectors.push_str("  ");
+    }
+    if sectors.is_empty() {
+        sectors.push(' ');
+    }
+    (lap, sectors)
+}
+
+/// Colours of the graph traces
+const YELLOW: Color = Color{r:1.,g:0.9,b:0.2,a:1.};
+const LIME: Color = Color{r:0.3,g:1.,b:0.3,a:1.};
+const SCARLET: Color = Color{r:1.,g:0.25,b:0.2,a:1.};
+const CYAN: Color = Color{r:0.3,g:0.9,b:1.,a:1.};
+
+/// Blue when cold, green at the optimal temperature and red when overheating
+fn tyre_colour(tyre: &Tyre, setup: &TyreSetup) -> Color {
+    let t = ((tyre.surface_temp - setup.optimal_temp) / setup.temp_window).max(-1.).min(1.);
+    if t < 0. {
+        Color::new(0., 1. + t, -t, 1.)
+    } else {
+        Color::new(t, 1. - t, 0., 1.)
     }
 }
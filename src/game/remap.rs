@@ -0,0 +1,92 @@
+use ::*;
+use ggez::graphics::{DrawMode, Rect};
+
+/// Darkens the game behind the screen
+const BACKGROUND: Color = Color{r:0.,g:0.,b:0.,a:0.8};
+/// Colour of the selected command
+const HIGHLIGHT: Color = Color{r:1.,g:0.85,b:0.2,a:1.};
+/// Size of the screen
+const SIZE: (f32, f32) = (640., 580.);
+/// Height of a row
+const ROW: f32 = 18.;
+
+/// A screen over the game for changing what the keys and buttons are bound to
+pub struct Remap {
+    commands: Vec<Command>,
+    selected: usize,
+    /// Whether the next key or button pressed is bound to the selected command
+    waiting: bool,
+    rect: Rect,
+    title: PosText,
+    rows: Vec<PosText>,
+}
+
+impl Remap {
+    /// Lays out the screen in the middle of a screen of the given size
+    pub fn new(a: &Assets, ctx: &mut Context, width: f32, height: f32) -> GameResult<Self> {
+        let rect = Rect::new((width - SIZE.0) / 2., (height - SIZE.1) / 2., SIZE.0, SIZE.1);
+        let (x, y) = (rect.x + 12., rect.y + 10.);
+        let commands = Command::all();
+        let mut rows = Vec::with_capacity(commands.len());
+        for i in 0..commands.len() {
+            rows.push(a.text(ctx, Point2::new(x, y + (i + 1) as f32 * ROW + 6.), " ")?);
+        }
+        Ok(Remap {
+            commands,
+            selected: 0,
+            waiting: false,
+            rect,
+            title: a.text(ctx, Point2::new(x, y), "Up/Down: choose  Return: add  Backspace: clear  Escape: save and close")?,
+            rows,
+        })
+    }
+    /// Handles a key or button being pressed, returning whether the screen stays open
+    ///
+    /// Escape gives up on adding a binding, so it can only be bound in the bindings file.
+    pub fn press(&mut self, binding: Binding, bindings: &mut Bindings) -> bool {
+        use self::Binding::{Key, Pad};
+        let command = self.commands[self.selected];
+        if self.waiting {
+            self.waiting = false;
+            if binding != Key(Keycode::Escape) {
+                bindings.bind(command, binding);
+            }
+            return true
+        }
+        let n = self.commands.len();
+        match binding {
+            Key(Keycode::Up) | Pad(Button::DPadUp) => self.selected = (self.selected + n - 1) % n,
+            Key(Keycode::Down) | Pad(Button::DPadDown) => self.selected = (self.selected + 1) % n,
+            Key(Keycode::Return) | Pad(Button::A) => self.waiting = true,
+            Key(Keycode::Backspace) | Key(Keycode::Delete) | Pad(Button::X) => bindings.clear(command),
+            Key(Keycode::Escape) | Pad(Button::B) => return false,
+            _ => (),
+        }
+        true
+    }
+    /// Writes out what's bound to each command
+    pub fn update(&mut self, a: &Assets, ctx: &mut Context, bindings: &Bindings) -> GameResult<()> {
+        for (i, (text, &command)) in self.rows.iter_mut().zip(&self.commands).enumerate() {
+            let bound = if self.waiting && i == self.selected {
+                "Press a key or button...".to_owned()
+            } else {
+                let bound: Vec<_> = bindings.bindings(command).iter().map(|b| b.to_string()).collect();
+                bound.join(", ")
+            };
+            text.update_text(a, ctx, &format!("{:<14} {}", command, bound))?;
+        }
+        Ok(())
+    }
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::set_color(ctx, BACKGROUND)?;
+        graphics::rectangle(ctx, DrawMode::Fill, self.rect)?;
+
+        graphics::set_color(ctx, graphics::WHITE)?;
+        self.title.draw_text(ctx)?;
+        for (i, text) in self.rows.iter().enumerate() {
+            graphics::set_color(ctx, if i == self.selected { HIGHLIGHT } else { graphics::WHITE })?;
+            text.draw_text(ctx)?;
+        }
+        graphics::set_color(ctx, graphics::WHITE)
+    }
+}
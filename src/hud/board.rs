@@ -0,0 +1,77 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Point2, Color, DrawMode, Rect};
+
+use sim::leaderboard::{Entry, TOP};
+use sim::timing::format_ticks;
+use ::{Assets, PosText};
+
+/// Darkens what's behind the table
+const BACKGROUND: Color = Color{r:0.,g:0.,b:0.,a:0.75};
+/// Colour of the row of the driver's own last run
+const HIGHLIGHT: Color = Color{r:1.,g:0.85,b:0.2,a:1.};
+/// Size of the table
+const SIZE: (f32, f32) = (560., 240.);
+/// Height of a row
+const ROW: f32 = 18.;
+
+/// A table of the fastest runs on a leaderboard in the middle of the screen
+pub struct Board {
+    rect: Rect,
+    title: PosText,
+    header: PosText,
+    rows: Vec<PosText>,
+    /// Row of the driver's own last run, if it made the table
+    highlight: Option<usize>,
+}
+
+impl Board {
+    /// Lays out the table in the middle of a screen of the given size
+    pub fn new(a: &Assets, ctx: &mut Context, width: f32, height: f32) -> GameResult<Self> {
+        let rect = Rect::new((width - SIZE.0) / 2., (height - SIZE.1) / 2., SIZE.0, SIZE.1);
+        let (x, y) = (rect.x + 12., rect.y + 10.);
+        let mut rows = Vec::with_capacity(TOP);
+        for i in 0..TOP {
+            rows.push(a.text(ctx, Point2::new(x, y + (i + 2) as f32 * ROW + 6.), " ")?);
+        }
+        Ok(Board {
+            rect,
+            title: a.text(ctx, Point2::new(x, y), " ")?,
+            header: a.text(ctx, Point2::new(x, y + ROW + 6.), &row("#", "Time", "Driver", "Date"))?,
+            rows,
+            highlight: None,
+        })
+    }
+    /// Fills in the table with `entries`, fastest first, highlighting the `highlight`th if given
+    pub fn update(&mut self, a: &Assets, ctx: &mut Context, title: &str, entries: &[&Entry], highlight: Option<usize>) -> GameResult<()> {
+        self.title.update_text(a, ctx, title)?;
+        for (i, text) in self.rows.iter_mut().enumerate() {
+            let line = match entries.get(i) {
+                Some(e) => row(&(i + 1).to_string(), &format_ticks(e.millis, 1000), &e.driver, &e.date.replace('T', " ").replace('Z', "")),
+                None if i == 0 => "No runs yet".to_owned(),
+                None => " ".to_owned(),
+            };
+            text.update_text(a, ctx, &line)?;
+        }
+        self.highlight = highlight;
+        Ok(())
+    }
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::set_color(ctx, BACKGROUND)?;
+        graphics::rectangle(ctx, DrawMode::Fill, self.rect)?;
+
+        graphics::set_color(ctx, graphics::WHITE)?;
+        self.title.draw_text(ctx)?;
+        self.header.draw_text(ctx)?;
+        for (i, text) in self.rows.iter().enumerate() {
+            graphics::set_color(ctx, if Some(i) == self.highlight { HIGHLIGHT } else { graphics::WHITE })?;
+            text.draw_text(ctx)?;
+        }
+        graphics::set_color(ctx, graphics::WHITE)
+    }
+}
+
+/// Lines up the columns of a row
+fn row(place: &str, time: &str, driver: &str, date: &str) -> String {
+    let driver: String = driver.chars().take(16).collect();
+    format!("{:>2}  {:>9}  {:<16}  {}", place, time, driver, date)
+}
@@ -0,0 +1,96 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Point2, Vector2, Color, DrawMode};
+
+use obj::setup::{CarSetup, AMBIENT_TEMP};
+use ::{Assets, PosText, Car, Dial, angle_to_vec};
+
+/// Colour of the redline and overheating zones
+const DANGER: Color = Color{r:0.9,g:0.1,b:0.1,a:1.};
+/// Colour of the tyre temperature zones
+const COLD: Color = Color{r:0.2,g:0.4,b:1.,a:1.};
+const WARM: Color = Color{r:0.2,g:0.8,b:0.2,a:1.};
+/// The shift light when it's lit and when it isn't
+const SHIFT_ON: Color = Color{r:0.3,g:0.6,b:1.,a:1.};
+const SHIFT_OFF: Color = Color{r:0.15,g:0.15,b:0.15,a:0.8};
+/// Radius of the tachometer and speedometer
+const BIG: f32 = 100.;
+/// Radius of the tyre temperature gauge
+const SMALL: f32 = 35.;
+
+/// Tachometer, speedometer and tyre temperature gauges, gear indicator and shift light
+pub struct Cluster {
+    tach: Dial,
+    speedo: Dial,
+    tyre_temp: Dial,
+    speedo_pos: Point2,
+    /// The top speed in m/s the speedometer goes up to
+    top_speed: f32,
+    gear_text: PosText,
+    speed_text: PosText,
+    shift_light: Point2,
+}
+
+impl Cluster {
+    /// Lays out the cluster in the bottom right of a screen of the given size
+    /// for a car with `setup` and a top speed in m/s
+    pub fn new(a: &Assets, ctx: &mut Context, setup: &CarSetup, top_speed: f32, width: f32, height: f32) -> GameResult<Self> {
+        let redline = setup.engine.redline_rpm;
+        let max_rpm = (redline / 1000.).ceil() * 1000. + 1000.;
+        let tyres = &setup.tyres;
+        let (cold, hot) = (tyres.optimal_temp - tyres.temp_window, tyres.optimal_temp + tyres.temp_window);
+        let max_temp = tyres.optimal_temp + 2. * tyres.temp_window;
+
+        let tach_pos = Point2::new(width - BIG - 10., height - BIG - 10.);
+        let speedo_pos = Point2::new(width - 3. * BIG - 30., height - BIG - 10.);
+        let mini_x = speedo_pos.x - BIG - SMALL - 15.;
+
+        Ok(Cluster {
+            tach: Dial::new(a, ctx, tach_pos, BIG, "RPM x1000", 0., max_rpm, 1000., Some(1000.))?
+                .zone(redline, max_rpm, DANGER),
+            speedo: speedo(a, ctx, speedo_pos, top_speed)?,
+            tyre_temp: Dial::new(a, ctx, Point2::new(mini_x, height - SMALL - 20.), SMALL, "Tyre °C", AMBIENT_TEMP, max_temp, 30., None)?
+                .zone(AMBIENT_TEMP, cold, COLD)
+                .zone(cold, hot, WARM)
+                .zone(hot, max_temp, DANGER),
+            speedo_pos,
+            top_speed,
+            gear_text: a.text_centred(ctx, tach_pos - Vector2::new(0., 0.35 * BIG), "N")?,
+            speed_text: a.text_centred(ctx, speedo_pos - Vector2::new(0., 0.35 * BIG), "000")?,
+            shift_light: tach_pos - Vector2::new(0., BIG + 15.),
+        })
+    }
+    /// Updates the digital readouts, and the speedometer if the top speed in m/s has changed
+    pub fn update(&mut self, a: &Assets, ctx: &mut Context, car: &Car, top_speed: f32) -> GameResult<()> {
+        if top_speed != self.top_speed {
+            self.speedo = speedo(a, ctx, self.speedo_pos, top_speed)?;
+            self.top_speed = top_speed;
+        }
+        self.gear_text.update_text(a, ctx, &car.setup.transmission.display(car.gear).to_string())?;
+        self.speed_text.update_text(a, ctx, &format!("{:3.0}", speed(car) * 3.6))
+    }
+    pub fn draw(&self, ctx: &mut Context, car: &Car) -> GameResult<()> {
+        let tyre_temp = car.tyres.iter().map(|t| t.surface_temp).sum::<f32>() / car.tyres.len() as f32;
+        self.tach.draw(ctx, car.engine_speed)?;
+        self.speedo.draw(ctx, speed(car) * 3.6)?;
+        self.tyre_temp.draw(ctx, tyre_temp)?;
+
+        graphics::set_color(ctx, graphics::WHITE)?;
+        self.gear_text.draw_text(ctx)?;
+        self.speed_text.draw_text(ctx)?;
+
+        let shift = car.setup.shift_rpm(car.gear).map(|rpm| car.engine_speed >= rpm).unwrap_or(false);
+        graphics::set_color(ctx, if shift { SHIFT_ON } else { SHIFT_OFF })?;
+        graphics::circle(ctx, DrawMode::Fill, self.shift_light, 9., 0.5)
+    }
+}
+
+/// A speedometer going a bit past a top speed in m/s
+fn speedo(a: &Assets, ctx: &mut Context, pos: Point2, top_speed: f32) -> GameResult<Dial> {
+    let max_speed = (top_speed * 3.6 / 40.).ceil() * 40.;
+    Dial::new(a, ctx, pos, BIG, "km/h", 0., max_speed, 40., Some(1.))
+}
+
+/// Speed along the direction of the car in m/s
+fn speed(car: &Car) -> f32 {
+    car.velocity.dot(&angle_to_vec(car.obj.rot)).abs()
+}
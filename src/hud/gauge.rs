@@ -0,0 +1,102 @@
+use std::f32::consts::PI;
+
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Point2, Vector2, Color, DrawMode};
+
+use ::{Assets, PosText, angle_to_vec};
+
+/// Colour of the face of a dial
+const FACE: Color = Color{r:0.05,g:0.05,b:0.05,a:0.8};
+/// Colour of the needle
+const NEEDLE: Color = Color{r:1.,g:0.35,b:0.1,a:1.};
+/// Angle on the screen of the lowest value, at the bottom left
+const START_ANGLE: f32 = 0.75 * PI;
+/// Angle the needle sweeps from the lowest to the highest value
+const SWEEP: f32 = 1.5 * PI;
+
+#[derive(Debug, Clone)]
+/// An analog dial with a needle, tick marks and coloured zones
+pub struct Dial {
+    centre: Point2,
+    radius: f32,
+    min: f32,
+    max: f32,
+    /// Value between tick marks
+    tick: f32,
+    /// Coloured arcs along the edge between two values
+    zones: Vec<(f32, f32, Color)>,
+    numbers: Vec<PosText>,
+    label: PosText,
+}
+
+impl Dial {
+    /// Makes a dial going from `min` to `max` with a tick mark every `tick`
+    ///
+    /// If `number_scale` is given, the ticks are numbered with their value divided by it
+    pub fn new(a: &Assets, ctx: &mut Context, centre: Point2, radius: f32, label: &str,
+            min: f32, max: f32, tick: f32, number_scale: Option<f32>) -> GameResult<Self> {
+        let mut dial = Dial {
+            centre,
+            radius,
+            min,
+            max,
+            tick,
+            zones: Vec::new(),
+            numbers: Vec::new(),
+            label: a.text_centred(ctx, centre + Vector2::new(0., 0.55 * radius), label)?,
+        };
+        if let Some(scale) = number_scale {
+            for i in 0..dial.ticks() + 1 {
+                let v = min + i as f32 * tick;
+                let pos = dial.point(v, 0.72);
+                dial.numbers.push(a.text_centred(ctx, pos, &format!("{:.0}", v / scale))?);
+            }
+        }
+        Ok(dial)
+    }
+    /// Colours the edge of the dial between two values
+    pub fn zone(mut self, from: f32, to: f32, colour: Color) -> Self {
+        self.zones.push((from, to, colour));
+        self
+    }
+    fn ticks(&self) -> u32 {
+        ((self.max - self.min) / self.tick + 0.001) as u32
+    }
+    /// Angle on the screen of the needle pointing at a value
+    fn angle(&self, v: f32) -> f32 {
+        let t = ((v - self.min) / (self.max - self.min)).max(0.).min(1.);
+        START_ANGLE + t * SWEEP
+    }
+    /// The point at a value on a circle of `r` times the radius
+    fn point(&self, v: f32, r: f32) -> Point2 {
+        self.centre + r * self.radius * angle_to_vec(self.angle(v))
+    }
+    /// Draws the dial with the needle pointing at `value`
+    pub fn draw(&self, ctx: &mut Context, value: f32) -> GameResult<()> {
+        graphics::set_color(ctx, FACE)?;
+        graphics::circle(ctx, DrawMode::Fill, self.centre, self.radius, 0.5)?;
+
+        for &(from, to, colour) in &self.zones {
+            let steps = ((self.angle(to) - self.angle(from)) / 0.05).ceil().max(1.) as u32;
+            let points: Vec<_> = (0..steps + 1)
+                .map(|i| self.point(from + (to - from) * i as f32 / steps as f32, 0.9))
+                .collect();
+            graphics::set_color(ctx, colour)?;
+            graphics::line(ctx, &points, 0.08 * self.radius)?;
+        }
+
+        graphics::set_color(ctx, graphics::WHITE)?;
+        for i in 0..self.ticks() + 1 {
+            let v = self.min + i as f32 * self.tick;
+            graphics::line(ctx, &[self.point(v, 0.82), self.point(v, 0.97)], 2.)?;
+        }
+        for number in &self.numbers {
+            number.draw_text(ctx)?;
+        }
+        self.label.draw_text(ctx)?;
+
+        graphics::set_color(ctx, NEEDLE)?;
+        graphics::line(ctx, &[self.point(value, -0.1), self.point(value, 0.85)], 3.)?;
+        graphics::circle(ctx, DrawMode::Fill, self.centre, 0.07 * self.radius, 0.5)
+    }
+}
@@ -0,0 +1,113 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Point2, Color, DrawMode, Rect};
+
+use ::{Assets, PosText};
+
+/// Background of a graph
+const BACKGROUND: Color = Color{r:0.,g:0.,b:0.,a:0.5};
+/// Colour of the axis at zero
+const AXIS: Color = Color{r:1.,g:1.,b:1.,a:0.3};
+
+#[derive(Debug, Clone)]
+/// A fixed number of samples where the oldest one is dropped when a new one is pushed to a full buffer
+pub struct RingBuffer {
+    samples: Vec<f32>,
+    /// Where the next sample goes, which is also the oldest sample when the buffer is full
+    next: usize,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    /// Makes an empty buffer with room for `capacity` samples
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer {
+            samples: Vec::with_capacity(capacity),
+            next: 0,
+            capacity,
+        }
+    }
+    /// Adds a sample, replacing the oldest one if the buffer is full
+    pub fn push(&mut self, v: f32) {
+        if self.samples.len() < self.capacity {
+            self.samples.push(v);
+        } else {
+            self.samples[self.next] = v;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+    /// The samples from oldest to newest
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item=&'a f32> {
+        // While filling up `next` is the end so the first part is empty
+        self.samples[self.next..].iter().chain(self.samples[..self.next].iter())
+    }
+    fn len(&self) -> usize {
+        self.samples.len()
+    }
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A rolling line graph of one or more traces sharing a range
+pub struct Graph {
+    rect: Rect,
+    min: f32,
+    max: f32,
+    traces: Vec<(Color, RingBuffer)>,
+    label: PosText,
+}
+
+impl Graph {
+    /// Makes a graph in `rect` on the screen showing values from `min` to `max`
+    ///
+    /// There is a trace for each colour keeping the last `samples` samples
+    pub fn new(a: &Assets, ctx: &mut Context, rect: Rect, label: &str, min: f32, max: f32, colours: &[Color], samples: usize) -> GameResult<Self> {
+        Ok(Graph {
+            rect,
+            min,
+            max,
+            traces: colours.iter().map(|&c| (c, RingBuffer::new(samples))).collect(),
+            label: a.text(ctx, Point2::new(rect.x + 2., rect.y + 1.), label)?,
+        })
+    }
+    /// Adds a sample to each trace in the order of the colours they were made with
+    pub fn push(&mut self, values: &[f32]) {
+        for (&mut (_, ref mut buffer), &v) in self.traces.iter_mut().zip(values) {
+            buffer.push(v);
+        }
+    }
+    /// The height on the screen of a value
+    fn y(&self, v: f32) -> f32 {
+        let t = ((v - self.min) / (self.max - self.min)).max(0.).min(1.);
+        self.rect.y + self.rect.h * (1. - t)
+    }
+    /// Draws the graph with the newest samples at the right edge
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let r = self.rect;
+        graphics::set_color(ctx, BACKGROUND)?;
+        graphics::rectangle(ctx, DrawMode::Fill, r)?;
+        if self.min < 0. && self.max > 0. {
+            let y = self.y(0.);
+            graphics::set_color(ctx, AXIS)?;
+            graphics::line(ctx, &[Point2::new(r.x, y), Point2::new(r.x + r.w, y)], 1.)?;
+        }
+
+        for &(colour, ref buffer) in &self.traces {
+            // A line needs at least two points
+            if buffer.len() < 2 {
+                continue
+            }
+            let spacing = r.w / (buffer.capacity() - 1) as f32;
+            let start = r.x + r.w - (buffer.len() - 1) as f32 * spacing;
+            let points: Vec<_> = buffer.iter().enumerate()
+                .map(|(i, &v)| Point2::new(start + i as f32 * spacing, self.y(v)))
+                .collect();
+            graphics::set_color(ctx, colour)?;
+            graphics::line(ctx, &points, 1.5)?;
+        }
+
+        graphics::set_color(ctx, graphics::WHITE)?;
+        self.label.draw_text(ctx)
+    }
+}
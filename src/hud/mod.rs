@@ -0,0 +1,49 @@
+//! Widgets for showing the state of the car on the HUD
+
+use std::str::FromStr;
+
+mod graph;
+mod gauge;
+mod cluster;
+mod board;
+
+pub use self::graph::*;
+pub use self::gauge::*;
+pub use self::cluster::*;
+pub use self::board::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How the engine speed, speed and gear are shown
+pub enum HudLayout {
+    /// Lines of text
+    Text,
+    /// A cluster of analog dials
+    Gauges,
+}
+
+impl Default for HudLayout {
+    fn default() -> Self {
+        HudLayout::Gauges
+    }
+}
+
+impl HudLayout {
+    /// The other layout
+    pub fn toggle(self) -> Self {
+        match self {
+            HudLayout::Text => HudLayout::Gauges,
+            HudLayout::Gauges => HudLayout::Text,
+        }
+    }
+}
+
+impl FromStr for HudLayout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(HudLayout::Text),
+            "gauges" => Ok(HudLayout::Gauges),
+            _ => Err(format!("Unknown HUD layout {}, expected text or gauges", s)),
+        }
+    }
+}
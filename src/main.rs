@@ -3,12 +3,14 @@
 
 extern crate ggez;
 extern crate self_compare;
+extern crate image;
 
 // use ggez::audio;
 use ggez::conf;
 use ggez::event::*;
 use ggez::{Context, ContextBuilder, GameResult};
 use ggez::timer;
+use std::fs;
 use ggez::graphics::{self, Vector2, Point2, Matrix4, Color};
 
 mod obj;
@@ -19,6 +21,14 @@ mod ext;
 pub use ext::*;
 mod game;
 pub use game::*;
+mod world;
+pub use world::*;
+mod opts;
+pub use opts::*;
+mod sim;
+pub use sim::*;
+mod hud;
+pub use hud::*;
 
 /// Makes a unit vector from a given direction angle
 fn angle_to_vec(angle: f32) -> Vector2 {
@@ -43,6 +53,101 @@ pub const RED: Color = Color{r:1.,g:0.,b:0.,a:0.5};
 pub const BLUE: Color = Color{r:0.,g:0.,b:1.,a:0.5};
 
 fn main() {
+    let opts = match Options::from_args() {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}", e);
+            return
+        }
+    };
+    if opts.integrator_report {
+        obj::print_report();
+        return
+    }
+    if let Some(ref path) = opts.record_golden {
+        let run = sim::golden::Run {
+            seed: opts.seed,
+            ticks: opts.ticks,
+            timestep: opts.timestep,
+        };
+        if let Err(e) = run.record(path) {
+            eprintln!("Couldn't record golden run: {}", e);
+        }
+        return
+    }
+    if let Some(ref path) = opts.verify_golden {
+        if !sim::golden::verify(path) {
+            ::std::process::exit(1);
+        }
+        return
+    }
+    // The benchmark and lap time simulation compare the setups given, or just drive the example car
+    let setups = if opts.setups.is_empty() {
+        vec![("example".to_owned(), obj::setup::example())]
+    } else {
+        opts.setups.clone()
+    };
+    if let Some(laps) = opts.benchmark {
+        for (i, &(ref name, ref setup)) in setups.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            println!("== {} ==", name);
+            let benchmark = sim::ai::Benchmark {
+                track: opts.world.file_name().to_owned(),
+                laps,
+                timestep: opts.timestep,
+                seed: opts.seed,
+                line: opts.racing_line.clone(),
+                setup: setup.clone(),
+            };
+            if let Err(e) = benchmark.run() {
+                eprintln!("Benchmark failed: {}", e);
+                ::std::process::exit(1);
+            }
+        }
+        return
+    }
+    if opts.lap_sim {
+        let limits = match opts.limits {
+            Some(ref path) => fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|src| sim::laptime::Limits::parse(path, &src)),
+            None => {
+                let path = sim::resources_dir().join("tracks").join(format!("{}.trk", opts.world.file_name()));
+                fs::read_to_string(&path)
+                    .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))
+                    .and_then(|src| world::Track::parse(&src))
+                    .map(|track| sim::laptime::Limits::from_track(&track))
+            }
+        };
+        let limits = match limits {
+            Ok(limits) => limits,
+            Err(e) => {
+                eprintln!("Lap time simulation failed: {}", e);
+                ::std::process::exit(1);
+            }
+        };
+        for (i, &(ref name, ref setup)) in setups.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            println!("== {} ==", name);
+            if let Err(e) = sim::laptime::analyse(&limits, setup.clone(), opts.save_line.as_ref().map(|s| &**s)) {
+                eprintln!("Lap time simulation failed: {}", e);
+                ::std::process::exit(1);
+            }
+        }
+        return
+    }
+
+    if let Some(addr) = opts.udp_listen {
+        if let Err(e) = sim::udp::listen(addr) {
+            eprintln!("Stopped listening for telemetry: {}", e);
+        }
+        return
+    }
+
     // Set window mode
     let window_mode = conf::WindowMode::default().dimensions(1000, 750);
 
@@ -61,7 +166,7 @@ fn main() {
     }
 
     // Tries to create a game state and runs it if succesful
-    match Master::new(&mut ctx) {
+    match Master::new(&mut ctx, opts) {
         Err(e) => {
             println!("Couldn't load game {}", e);
         }
@@ -1,18 +1,94 @@
-use std::f32::consts::{PI, FRAC_PI_2, FRAC_PI_4};
+use std::f32::consts::FRAC_PI_4;
 
 use ext::FloatExt;
-use super::setup::{CarSetup, example};
+use super::setup::{CarSetup, example, AMBIENT_TEMP, G};
 use super::*;
-use ::{InputState, PIXELS_PER_METER, DELTA, angle_to_vec};
+use ::{Surface, Sprite, PIXELS_PER_METER, angle_to_vec};
+use sim::Checksum;
+
+/// How fast the tyres scrub off spin from collisions, higher is faster
+const SPIN_DECAY: f32 = 3.;
+/// Seconds the tyres take to stop the car sliding sideways when they have the grip for it
+const GRIP_TIME: f32 = 0.05;
+/// Speed in m/s below which the brakes ease off so they stop the car instead of pushing it backwards
+const BRAKE_STOP_SPEED: f32 = 1.;
+
+#[derive(Debug, Clone, Copy)]
+/// The forces acting on a car in N
+pub struct Forces {
+    pub traction: Vector2,
+    pub drag: Vector2,
+    pub grip: Vector2,
+    pub brake: Vector2,
+    /// Braking force asked of the front brakes
+    pub front_brake: f32,
+    /// Braking force asked of the rear brakes
+    pub rear_brake: f32,
+    /// Normal force on each tyre
+    pub loads: [f32; 4],
+}
+
+impl Forces {
+    /// The sum of all the forces
+    pub fn total(&self) -> Vector2 {
+        self.traction + self.drag + self.grip + self.brake
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// What went on in the car during its last step, for telemetry
+pub struct Diagnostics {
+    pub forces: Forces,
+    /// The surface the car was on
+    pub surface: Surface,
+    /// The acceleration in m/s²
+    pub acc: Vector2,
+}
+
+/// The dynamics of a car on a surface
+struct CarDynamics<'a> {
+    car: &'a Car,
+    surface: Surface,
+}
+
+impl<'a> Dynamics for CarDynamics<'a> {
+    fn kinematics(&self, body: &Body) -> (Vector2, f32) {
+        let setup = &self.car.setup;
+        let ang = angle_to_vec(body.obj.rot + self.car.steering_angle);
+        let car_ang = angle_to_vec(body.obj.rot);
+        let car_ang_kryds = Vector2::new(car_ang.y, -car_ang.x);
+
+        // The car turns around its rear axle, plus any spin from being hit
+        let yaw_rate = body.velocity.dot(&ang) * self.car.steering_angle.sin() / (setup.rw_dist + setup.fw_dist);
+        let pos = (body.velocity + setup.rw_dist * yaw_rate * car_ang_kryds) * PIXELS_PER_METER;
+        (pos, yaw_rate + self.car.spin)
+    }
+    fn acceleration(&self, body: &Body) -> Vector2 {
+        self.car.forces(body, self.surface).total() / self.car.setup.mass
+    }
+}
 
 #[derive(Debug)]
 pub struct Car {
     pub obj: Object,
     pub velocity: Vector2,
+    /// Yaw rate in rad/s from collisions that the tyres haven't scrubbed off yet
+    pub spin: f32,
+    /// What the car looks like, which also decides how big it is
+    pub sprite: Sprite,
     pub setup: CarSetup,
     pub engine_speed: f32,
     pub steering_angle: f32,
     pub brake: f32,
+    /// How far the handbrake is pulled from 0 to 1
+    pub handbrake: f32,
+    /// Temperatures of the front and rear brake discs in °C
+    pub brake_temp: [f32; 2],
+    /// The front left, front right, rear left and rear right tyres
+    pub tyres: [Tyre; 4],
+    pub suspension: Suspension,
+    /// Forces and such from the last step, if there has been one
+    pub diagnostics: Option<Diagnostics>,
     pub throttle: f32,
     pub clutch: f32,
     pub gear: i8,
@@ -24,121 +100,221 @@ impl Car {
         Car {
             obj: Object::new(Point2::new(x, y)),
             velocity: Vector2::new(0., 0.),
+            spin: 0.,
+            sprite: Sprite::Ferrari,
             engine_speed: setup.engine.idle_rpm,
             setup,
             steering_angle: 0.,
             brake: 0.,
+            handbrake: 0.,
+            brake_temp: [AMBIENT_TEMP; 2],
+            tyres: [Tyre::new(); 4],
+            suspension: Suspension::default(),
+            diagnostics: None,
             throttle: 0.,
             clutch: 1.,
             gear: 0,
         }
     }
-    pub fn update(&mut self, input: &InputState) {
-        let ang = angle_to_vec(self.obj.rot+self.steering_angle);
-        let side_ang = Vector2::new(ang.y, -ang.x);
+    pub fn update(&mut self, controls: &Controls, surface: Surface, dt: f32, integrator: Integrator) {
+        self.update_controls(controls);
+
+        let body = self.body();
+        let forces = self.forces(&body, surface);
+        let ang = angle_to_vec(self.obj.rot + self.steering_angle);
         let speed_forwards = self.velocity.dot(&ang);
         let speed_sideways = self.velocity.perp(&ang);
+        let speed = self.velocity.norm();
 
-        let input_hor = input.hor();
-
-        if input_hor == 0. {
-            if self.steering_angle.abs() <= 2. * PI * DELTA {
-                self.steering_angle = 0.;
-            } else {
-                self.steering_angle -= self.steering_angle.signum() * 2. * PI * DELTA;
-            }
-        } else {
-            self.steering_angle += input_hor * FRAC_PI_2 * DELTA;
-            if self.steering_angle.abs() > FRAC_PI_4 {
-                self.steering_angle = self.steering_angle.signum() * FRAC_PI_4;
+        if self.gear == 0 {
+            self.engine_speed += self.throttle * self.setup.engine.get_torque(self.engine_speed) * dt / 0.1;
+            if self.throttle == 0. {
+                self.engine_speed.cap_sub(1000. * dt, self.setup.engine.idle_rpm);
             }
+        } else if self.clutch == 1. {
+            self.engine_speed += self.throttle * self.setup.engine.get_torque(self.engine_speed) * dt;
         }
 
-        let car_ang = angle_to_vec(self.obj.rot);
-        let car_ang_kryds = Vector2::new(car_ang.y, -car_ang.x);
+        // The work done by the brakes ends up as heat in the discs
+        let brakes = self.setup.brakes;
+        let (mut front_energy, mut rear_energy) = (0., 0.);
+        let requested = forces.front_brake + forces.rear_brake;
+        if requested > 0. {
+            let energy = forces.brake.norm() * speed * dt;
+            front_energy = energy * forces.front_brake / requested;
+            rear_energy = energy * forces.rear_brake / requested;
+        }
+        self.brake_temp[0] = brakes.heat(self.brake_temp[0], front_energy, speed, dt);
+        self.brake_temp[1] = brakes.heat(self.brake_temp[1], rear_energy, speed, dt);
 
-        let angle_diff = speed_forwards * self.steering_angle.sin() / (self.setup.rw_dist + self.setup.fw_dist) * DELTA;
-        self.obj.pos += self.setup.rw_dist * (angle_diff.sin()*car_ang_kryds - (1. - angle_diff.cos())*car_ang) * PIXELS_PER_METER;
-        self.obj.rot += angle_diff;
+        // Spread the slip energy over the tyres by how much load they carry
+        let slip_energy = forces.grip.norm() * speed_sideways.abs() * dt;
+        let total_load = forces.loads.iter().sum::<f32>();
+        for (tyre, load) in self.tyres.iter_mut().zip(forces.loads.iter()) {
+            tyre.update(&self.setup.tyres, load / total_load * slip_energy, speed, dt);
+        }
 
-        // NOTE implement using clutch
+        let next = integrator.step(&CarDynamics { car: self, surface }, &body, dt);
+        self.obj = next.obj;
+        self.velocity = next.velocity;
+        self.spin *= (-SPIN_DECAY * dt).exp();
 
-        let rr = -self.setup.rolling_r * speed_forwards;
-        let mut traction_f = rr;
-        if input.acltr() {
-            self.throttle.cap_add(4. * DELTA, 1.);
-        } else {
-            self.throttle.cap_sub(8. * DELTA, 0.);
+        let car_ang = angle_to_vec(body.obj.rot);
+        let car_ang_kryds = Vector2::new(car_ang.y, -car_ang.x);
+        let acc = forces.total() / self.setup.mass;
+        self.suspension.update(&self.setup, acc.dot(&car_ang_kryds), acc.dot(&car_ang), dt);
+        self.diagnostics = Some(Diagnostics {
+            forces,
+            surface,
+            acc,
+        });
+
+        if self.clutch == 0. && self.gear != 0 {
+            self.engine_speed = self.setup.get_engine_rpm(speed_forwards, self.gear);
         }
-        if self.engine_speed >= self.setup.engine.redline_rpm {
-            self.throttle = 0.;
+
+        if self.engine_speed <= self.setup.engine.idle_rpm {
+            self.clutch = 1. - self.throttle * 0.5;
+        } else {
+            self.clutch = 0.;
         }
-        if input.brk() {
-            if self.brake >= 0.5 {
-                self.brake += 8. * DELTA;
-            }
-            self.brake.cap_add(8. * DELTA, 1.);
+        // The driver can always hold the clutch down further than it would be
+        self.clutch = self.clutch.max(controls.clutch.max(0.).min(1.));
+    }
+    /// Puts the steering wheel and pedals where the controls have them
+    fn update_controls(&mut self, controls: &Controls) {
+        let controls = controls.clamped();
+        self.steering_angle = controls.steering * FRAC_PI_4;
+        // The engine cuts out at the redline
+        self.throttle = if self.engine_speed >= self.setup.engine.redline_rpm {
+            0.
         } else {
-            self.brake.cap_sub(16. * DELTA, 0.);
+            controls.throttle
+        };
+        self.brake = controls.brake;
+        self.handbrake = controls.handbrake;
+    }
+    /// How fast the car is turning in rad/s
+    pub fn yaw_rate(&self) -> f32 {
+        let ang = angle_to_vec(self.obj.rot + self.steering_angle);
+        let wheelbase = self.setup.rw_dist + self.setup.fw_dist;
+        self.velocity.dot(&ang) * self.steering_angle.sin() / wheelbase + self.spin
+    }
+    /// The box the car takes up
+    pub fn obb(&self) -> Obb {
+        Obb::around(&self.obj, self.sprite)
+    }
+    /// The position, rotation and velocity of the car
+    pub fn body(&self) -> Body {
+        Body {
+            obj: self.obj,
+            velocity: self.velocity,
         }
+    }
+    /// The forces acting on the car if it were in the state of `body` with the current controls
+    ///
+    /// They don't depend on the step size, so only the integrator decides how well they're followed
+    pub fn forces(&self, body: &Body, surface: Surface) -> Forces {
+        let ang = angle_to_vec(body.obj.rot + self.steering_angle);
+        let side_ang = Vector2::new(ang.y, -ang.x);
+        let speed_forwards = body.velocity.dot(&ang);
+        let speed_sideways = body.velocity.perp(&ang);
+        let speed = body.velocity.norm();
 
-        traction_f += self.setup.get_drive_force(self.engine_speed, self.gear, self.throttle);
-
-        let drag = -self.setup.drag * self.velocity.norm() * self.velocity;
-        let grip_force = -speed_sideways * side_ang * self.setup.mass / DELTA;
-
-        let gear_rpm = self.setup.get_engine_rpm(speed_forwards, self.gear);
-
-        let traction;
+        // NOTE implement using clutch
 
-        if self.gear == 0 {
-            traction = Vector2::new(0., 0.);
+        let rr = -surface.rolling_r().unwrap_or(self.setup.rolling_r) * speed_forwards;
+        let traction_f = rr + self.setup.get_drive_force(self.engine_speed, self.gear, self.throttle);
 
-            self.engine_speed += self.throttle * self.setup.engine.get_torque(self.engine_speed) * DELTA / 0.1;
-            if self.throttle == 0. {
-                self.engine_speed.cap_sub(1000. * DELTA, self.setup.engine.idle_rpm);
-            }
+        let traction = if self.gear == 0 || self.clutch == 1. {
+            Vector2::new(0., 0.)
+        } else if self.clutch == 0. {
+            traction_f * ang
         } else {
-            traction = if self.clutch == 0. {
-                traction_f
-            } else if self.clutch == 1. {
-                self.engine_speed += self.throttle * self.setup.engine.get_torque(self.engine_speed) * DELTA;
+            let gear_rpm = self.setup.get_engine_rpm(speed_forwards, self.gear);
+            let rpm_diff = self.engine_speed - gear_rpm;
 
-                0.
-            } else {
-                let rpm_diff = self.engine_speed - gear_rpm;
+            (1. - (rpm_diff / 500.).max(0.).min(1.)) * (1. - self.clutch) * traction_f * ang
+        };
 
-                (1. - (rpm_diff / 500.).max(0.).min(1.)) * (1. - self.clutch) * traction_f
-            } * ang;
-        }
+        let drag = -self.setup.drag_coefficient() * speed * body.velocity;
 
-        let brake_force;
-        if speed_forwards != 0. || speed_sideways != 0. {
-            let brake = self.setup.brake_force * self.brake;
-            let max_brake = self.velocity.norm() * self.velocity.norm() * self.setup.mass / DELTA;
-            if brake > max_brake {
-                brake_force = -max_brake * self.velocity.normalize();
-            } else {
-                brake_force = -brake * self.velocity.normalize();
-            }
-        } else {
-            brake_force = Vector2::new(0., 0.);
+        let mut grip = -speed_sideways * side_ang * self.setup.mass / GRIP_TIME;
+        let friction = surface.friction().unwrap_or(self.setup.tyres.friction);
+        let loads = self.suspension.corner_loads(&self.setup, speed);
+        let max_grip = self.max_grip(friction, &loads);
+        let grip_norm = grip.norm();
+        if grip_norm > max_grip {
+            grip *= max_grip / grip_norm;
         }
 
-        let total_force = traction + drag + grip_force + brake_force;
-        let acc = total_force / self.setup.mass;
+        let brakes = &self.setup.brakes;
+        let (front_torque, rear_torque) = brakes.get_torques(self.brake);
+        // The handbrake pulls the rear brakes on whatever the bias is
+        let rear_torque = rear_torque.max(self.handbrake * brakes.rear_torque);
+        let front_brake = front_torque * brakes.friction(self.brake_temp[0]) / self.setup.wheel_radius;
+        let rear_brake = rear_torque * brakes.friction(self.brake_temp[1]) / self.setup.wheel_radius;
 
-        self.obj.pos += (self.velocity * DELTA + 0.5 * acc * DELTA * DELTA) * PIXELS_PER_METER;
-        self.velocity += acc * DELTA;
+        let brake = if speed != 0. {
+            -(front_brake + rear_brake) * (speed / BRAKE_STOP_SPEED).min(1.) * body.velocity.normalize()
+        } else {
+            Vector2::new(0., 0.)
+        };
 
-        if self.clutch == 0. && self.gear != 0 {
-            self.engine_speed = self.setup.get_engine_rpm(speed_forwards, self.gear);
+        Forces {
+            traction,
+            drag,
+            grip,
+            brake,
+            front_brake,
+            rear_brake,
+            loads,
         }
+    }
+    /// The most grip in N all the tyres can give together with the given loads on a surface with `friction`
+    pub fn max_grip(&self, friction: f32, loads: &[f32; 4]) -> f32 {
+        let tyres = &self.setup.tyres;
+        let front = self.setup.front_weight();
+        let weight = self.setup.mass * G;
+        let static_loads = [0.5 * front * weight, 0.5 * front * weight, 0.5 * (1. - front) * weight, 0.5 * (1. - front) * weight];
 
-        if self.engine_speed <= self.setup.engine.idle_rpm {
-            self.clutch = 1. - self.throttle * 0.5;
-        } else {
-            self.clutch = 0.;
+        self.tyres.iter().zip(loads.iter().zip(static_loads.iter()))
+            .map(|(tyre, (&load, &static_load))| tyres.load_friction(friction, load, static_load) * tyre.grip(tyres) * load)
+            .sum()
+    }
+    /// The lateral (positive to the left) and longitudinal acceleration in g during the last step
+    pub fn g_forces(&self) -> (f32, f32) {
+        let acc = match self.diagnostics {
+            Some(d) => d.acc,
+            None => return (0., 0.),
+        };
+        let forwards = angle_to_vec(self.obj.rot);
+        let left = Vector2::new(forwards.y, -forwards.x);
+        (acc.dot(&left) / G, acc.dot(&forwards) / G)
+    }
+    /// A checksum of the exact state of the car
+    ///
+    /// Two cars with the same checksum will almost certainly behave the same from then on
+    pub fn checksum(&self) -> u64 {
+        let mut sum = Checksum::new();
+        for &v in &[self.obj.pos.x, self.obj.pos.y, self.obj.rot, self.velocity.x, self.velocity.y,
+                    self.engine_speed, self.steering_angle, self.brake, self.handbrake, self.throttle, self.clutch] {
+            sum.f32(v);
+        }
+        sum.f32(self.spin);
+        sum.bytes(&[self.gear as u8]);
+        for &temp in &self.brake_temp {
+            sum.f32(temp);
+        }
+        for tyre in &self.tyres {
+            sum.f32(tyre.surface_temp);
+            sum.f32(tyre.core_temp);
+            sum.f32(tyre.wear);
+        }
+        let s = &self.suspension;
+        for &v in &[s.roll, s.roll_rate, s.pitch, s.pitch_rate] {
+            sum.f32(v);
         }
+        sum.0
     }
 }
@@ -0,0 +1,186 @@
+//! Oriented bounding boxes and what happens when they hit each other
+
+use super::*;
+use ::{Sprite, PIXELS_PER_METER, angle_to_vec};
+
+#[derive(Debug, Clone, Copy)]
+/// A rectangle that can be turned, in pixels
+pub struct Obb {
+    pub centre: Point2,
+    /// Half the length along the rotation and half the width across it
+    pub half: Vector2,
+    pub rot: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Where two boxes overlap
+pub struct Contact {
+    /// The deepest point of the overlap
+    pub point: Point2,
+    /// Unit vector pointing out of the other box into this one
+    pub normal: Vector2,
+    /// How far the boxes overlap along the normal in pixels
+    pub depth: f32,
+}
+
+impl Obb {
+    pub fn new(centre: Point2, length: f32, width: f32, rot: f32) -> Self {
+        Obb {
+            centre,
+            half: Vector2::new(0.5 * length, 0.5 * width),
+            rot,
+        }
+    }
+    /// The box around a sprite drawn at `obj`
+    pub fn around(obj: &Object, sprite: Sprite) -> Self {
+        Obb::new(obj.pos, sprite.width(), sprite.height(), obj.rot)
+    }
+    /// A box of some thickness along the line from `a` to `b`, covering the ends too
+    pub fn segment(a: Point2, b: Point2, thickness: f32) -> Self {
+        let ab = b - a;
+        Obb::new(a + 0.5 * ab, ab.norm() + thickness, thickness, ab.y.atan2(ab.x))
+    }
+    /// Unit vectors along the length and width of the box
+    pub fn axes(&self) -> [Vector2; 2] {
+        let along = angle_to_vec(self.rot);
+        [along, Vector2::new(-along.y, along.x)]
+    }
+    pub fn corners(&self) -> [Point2; 4] {
+        let [along, across] = self.axes();
+        let (l, w) = (self.half.x * along, self.half.y * across);
+        let c = self.centre;
+        [c + l + w, c + l - w, c - l - w, c - l + w]
+    }
+    /// Radius of the circle around the box
+    pub fn radius(&self) -> f32 {
+        self.half.norm()
+    }
+    /// How far the box reaches from its centre along `axis`
+    fn reach(&self, axis: Vector2) -> f32 {
+        let [along, across] = self.axes();
+        self.half.x * along.dot(&axis).abs() + self.half.y * across.dot(&axis).abs()
+    }
+    /// Where this box overlaps `other`, if it does, by the separating axis theorem
+    pub fn collide(&self, other: &Obb) -> Option<Contact> {
+        let d = self.centre - other.centre;
+        let reach = self.radius() + other.radius();
+        if d.norm_squared() > reach * reach {
+            return None
+        }
+
+        // The axis the boxes overlap the least along, and whether it's one of `self`'s
+        let mut best: Option<(f32, Vector2, bool)> = None;
+        let [a0, a1] = self.axes();
+        let [b0, b1] = other.axes();
+        for &(axis, own) in &[(a0, true), (a1, true), (b0, false), (b1, false)] {
+            let dist = d.dot(&axis);
+            let overlap = self.reach(axis) + other.reach(axis) - dist.abs();
+            if overlap <= 0. {
+                return None
+            }
+            if best.map(|(o, _, _)| overlap < o).unwrap_or(true) {
+                best = Some((overlap, if dist < 0. { -axis } else { axis }, own));
+            }
+        }
+
+        let (depth, normal, own) = best.unwrap();
+        let deepest = |corners: [Point2; 4], sign: f32| {
+            let depth = |p: &Point2| sign * p.coords.dot(&normal);
+            corners.iter().cloned().fold(corners[0], |a, b| if depth(&b) > depth(&a) { b } else { a })
+        };
+        // The corner that has gone furthest into the box whose face the normal is on
+        let point = if own {
+            deepest(other.corners(), 1.)
+        } else {
+            deepest(self.corners(), -1.)
+        };
+        Some(Contact {
+            point,
+            normal,
+            depth,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// How things bounce off and slide along something
+pub struct Material {
+    /// How much of the speed into it is kept bouncing back off it, from 0 to 1
+    pub restitution: f32,
+    /// Friction coefficient when sliding along it
+    pub friction: f32,
+}
+
+impl Material {
+    /// Concrete walls
+    pub const CONCRETE: Material = Material { restitution: 0.2, friction: 0.6 };
+    /// Barriers made of stacked tyres
+    pub const TYRES: Material = Material { restitution: 0.5, friction: 0.8 };
+    /// Traffic cones
+    pub const CONE: Material = Material { restitution: 0.1, friction: 0.3 };
+    /// Other cars
+    pub const CAR: Material = Material { restitution: 0.3, friction: 0.4 };
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Something in the world that doesn't move when hit
+pub struct Collider {
+    pub obb: Obb,
+    pub material: Material,
+}
+
+/// Velocity in m/s of a point `r` m from the centre of a car
+fn point_velocity(car: &Car, r: Vector2) -> Vector2 {
+    car.velocity + car.yaw_rate() * Vector2::new(-r.y, r.x)
+}
+
+/// The 2D cross product
+fn cross(a: Vector2, b: Vector2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Pushes `a` out of `b`, or out of something that doesn't move if there is no `b`,
+/// and changes their velocities and spins by the impulse of the collision
+///
+/// The contact's normal has to point from `b` into `a`
+pub fn resolve(contact: &Contact, material: Material, a: &mut Car, mut b: Option<&mut Car>) {
+    let n = contact.normal;
+    let inverse = |car: &Car| (1. / car.setup.mass, 1. / car.setup.yaw_inertia());
+    let (inv_ma, inv_ia) = inverse(a);
+    let (inv_mb, inv_ib) = b.as_ref().map(|b| inverse(b)).unwrap_or((0., 0.));
+
+    // Move them apart by how heavy they are so they don't stay stuck in each other
+    let share = inv_ma / (inv_ma + inv_mb);
+    a.obj.pos += share * contact.depth * n;
+    if let Some(ref mut b) = b {
+        b.obj.pos -= (1. - share) * contact.depth * n;
+    }
+
+    let ra = (contact.point - a.obj.pos) / PIXELS_PER_METER;
+    let rb = b.as_ref().map(|b| (contact.point - b.obj.pos) / PIXELS_PER_METER).unwrap_or_else(Vector2::zeros);
+    let vb = b.as_ref().map(|b| point_velocity(b, rb)).unwrap_or_else(Vector2::zeros);
+    let v = point_velocity(a, ra) - vb;
+    let vn = v.dot(&n);
+    // Already moving apart
+    if vn >= 0. {
+        return
+    }
+    // How hard it is to change the relative velocity along a direction
+    let stiffness = |dir: Vector2| inv_ma + inv_mb + cross(ra, dir).powi(2) * inv_ia + cross(rb, dir).powi(2) * inv_ib;
+
+    let j = -(1. + material.restitution) * vn / stiffness(n);
+    let mut impulse = j * n;
+    let sliding = v - vn * n;
+    if sliding.norm() > 1e-6 {
+        let t = sliding.normalize();
+        let jt = (-v.dot(&t) / stiffness(t)).max(-material.friction * j).min(material.friction * j);
+        impulse += jt * t;
+    }
+
+    a.velocity += inv_ma * impulse;
+    a.spin += inv_ia * cross(ra, impulse);
+    if let Some(b) = b {
+        b.velocity -= inv_mb * impulse;
+        b.spin -= inv_ib * cross(rb, impulse);
+    }
+}
@@ -0,0 +1,92 @@
+use ext::FloatExt;
+use ::InputState;
+
+/// How fast the steering turns towards full lock when a steering key is held, in locks per second
+const STEER_RATE: f32 = 2.;
+/// How fast the steering straightens up when no steering key is held, in locks per second
+const CENTRE_RATE: f32 = 8.;
+/// How fast the throttle opens and closes per second
+const THROTTLE_RATE: (f32, f32) = (4., 8.);
+/// How fast the brake is pressed and let go per second
+const BRAKE_RATE: (f32, f32) = (8., 16.);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Where the driver holds the pedals and the steering wheel
+pub struct Controls {
+    /// How far the throttle is pressed from 0 to 1
+    pub throttle: f32,
+    /// How far the brake is pressed from 0 to 1
+    pub brake: f32,
+    /// How far the clutch pedal is pressed from 0 to 1, on top of what the automatic clutch does
+    pub clutch: f32,
+    /// Steering from -1 for full lock to the left to 1 for full lock to the right
+    pub steering: f32,
+    /// How far the handbrake is pulled from 0 to 1
+    pub handbrake: f32,
+}
+
+impl Controls {
+    /// The controls with every value moved into its range
+    pub fn clamped(self) -> Self {
+        let unit = |v: f32| v.max(0.).min(1.);
+        Controls {
+            throttle: unit(self.throttle),
+            brake: unit(self.brake),
+            clutch: unit(self.clutch),
+            steering: self.steering.max(-1.).min(1.),
+            handbrake: unit(self.handbrake),
+        }
+    }
+    /// Both controls at once, with each pedal pressed and the steering turned as far as either has it
+    pub fn merge(self, other: Controls) -> Self {
+        Controls {
+            throttle: self.throttle.max(other.throttle),
+            brake: self.brake.max(other.brake),
+            clutch: self.clutch.max(other.clutch),
+            steering: if other.steering.abs() > self.steering.abs() { other.steering } else { self.steering },
+            handbrake: self.handbrake.max(other.handbrake),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Turns the keys being held into controls by moving the pedals and steering at fixed rates
+pub struct KeyboardRamp {
+    controls: Controls,
+}
+
+impl KeyboardRamp {
+    /// Moves the controls towards what the keys ask for over `dt` seconds
+    pub fn update(&mut self, keys: &InputState, dt: f32) -> Controls {
+        let c = &mut self.controls;
+
+        let hor = keys.hor();
+        if hor == 0. {
+            if c.steering.abs() <= CENTRE_RATE * dt {
+                c.steering = 0.;
+            } else {
+                c.steering -= c.steering.signum() * CENTRE_RATE * dt;
+            }
+        } else {
+            c.steering = (c.steering + hor * STEER_RATE * dt).max(-1.).min(1.);
+        }
+
+        if keys.acltr() {
+            c.throttle.cap_add(THROTTLE_RATE.0 * dt, 1.);
+        } else {
+            c.throttle.cap_sub(THROTTLE_RATE.1 * dt, 0.);
+        }
+        if keys.brk() {
+            // Pressing on gets harder faster once the brake bites
+            if c.brake >= 0.5 {
+                c.brake += BRAKE_RATE.0 * dt;
+            }
+            c.brake.cap_add(BRAKE_RATE.0 * dt, 1.);
+        } else {
+            c.brake.cap_sub(BRAKE_RATE.1 * dt, 0.);
+        }
+        // The handbrake is a lever that's either pulled or not
+        c.handbrake = if keys.handbrake != 0 { 1. } else { 0. };
+        *c
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::*;
+use ::{Surface, PIXELS_PER_METER};
+
+#[derive(Debug, Clone, Copy)]
+/// The part of a car's state that is integrated over time
+pub struct Body {
+    /// Position in pixels and rotation in radians
+    pub obj: Object,
+    /// Velocity in m/s
+    pub velocity: Vector2,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// How fast a `Body` changes
+pub struct BodyRate {
+    /// Change in position in pixels per second
+    pub pos: Vector2,
+    /// Change in rotation in radians per second
+    pub rot: f32,
+    /// Acceleration in m/s²
+    pub acc: Vector2,
+}
+
+impl Body {
+    /// Moves the body along the given rate for `dt` seconds
+    pub fn advance(&self, rate: &BodyRate, dt: f32) -> Body {
+        Body {
+            obj: Object {
+                pos: self.obj.pos + rate.pos * dt,
+                rot: self.obj.rot + rate.rot * dt,
+            },
+            velocity: self.velocity + rate.acc * dt,
+        }
+    }
+}
+
+/// Something that knows how a `Body` moves
+pub trait Dynamics {
+    /// How fast the position (in pixels per second) and rotation change in the given state
+    fn kinematics(&self, body: &Body) -> (Vector2, f32);
+    /// The acceleration in m/s² in the given state
+    fn acceleration(&self, body: &Body) -> Vector2;
+
+    /// The full rate of change in the given state
+    fn rate(&self, body: &Body) -> BodyRate {
+        let (pos, rot) = self.kinematics(body);
+        BodyRate {
+            pos,
+            rot,
+            acc: self.acceleration(body),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Numerical methods for stepping a `Body` forwards in time
+pub enum Integrator {
+    /// Moves everything along the rates at the start of the step
+    Euler,
+    /// Updates the velocity first and moves with the new velocity
+    SemiImplicitEuler,
+    /// Velocity Verlet, averaging the acceleration at the start and the end of the step
+    Verlet,
+    /// Classic fourth order Runge-Kutta
+    Rk4,
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Integrator::Verlet
+    }
+}
+
+impl Integrator {
+    /// Every integrator
+    pub const ALL: [Integrator; 4] = [Integrator::Euler, Integrator::SemiImplicitEuler, Integrator::Verlet, Integrator::Rk4];
+
+    /// Steps `body` forwards by `dt` seconds
+    pub fn step<D: Dynamics>(self, dynamics: &D, body: &Body, dt: f32) -> Body {
+        match self {
+            Integrator::Euler => body.advance(&dynamics.rate(body), dt),
+            Integrator::SemiImplicitEuler => {
+                let velocity = body.velocity + dynamics.acceleration(body) * dt;
+                let (pos, rot) = dynamics.kinematics(&Body { velocity, .. *body });
+                Body {
+                    obj: Object {
+                        pos: body.obj.pos + pos * dt,
+                        rot: body.obj.rot + rot * dt,
+                    },
+                    velocity,
+                }
+            }
+            Integrator::Verlet => {
+                let (pos, rot) = dynamics.kinematics(body);
+                let acc = dynamics.acceleration(body);
+                let obj = Object {
+                    pos: body.obj.pos + pos * dt + 0.5 * acc * dt * dt * PIXELS_PER_METER,
+                    rot: body.obj.rot + rot * dt,
+                };
+                // The forces depend on the velocity, so the end acceleration is found with a predicted velocity
+                let predicted = Body { obj, velocity: body.velocity + acc * dt };
+                let end_acc = dynamics.acceleration(&predicted);
+                Body {
+                    obj,
+                    velocity: body.velocity + 0.5 * (acc + end_acc) * dt,
+                }
+            }
+            Integrator::Rk4 => {
+                let k1 = dynamics.rate(body);
+                let k2 = dynamics.rate(&body.advance(&k1, 0.5 * dt));
+                let k3 = dynamics.rate(&body.advance(&k2, 0.5 * dt));
+                let k4 = dynamics.rate(&body.advance(&k3, dt));
+                body.advance(&BodyRate {
+                    pos: (k1.pos + 2. * k2.pos + 2. * k3.pos + k4.pos) / 6.,
+                    rot: (k1.rot + 2. * k2.rot + 2. * k3.rot + k4.rot) / 6.,
+                    acc: (k1.acc + 2. * k2.acc + 2. * k3.acc + k4.acc) / 6.,
+                }, dt)
+            }
+        }
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "euler" => Ok(Integrator::Euler),
+            "semi-implicit" => Ok(Integrator::SemiImplicitEuler),
+            "verlet" => Ok(Integrator::Verlet),
+            "rk4" => Ok(Integrator::Rk4),
+            _ => Err(format!("Unknown integrator {}, expected euler, semi-implicit, verlet or rk4", s)),
+        }
+    }
+}
+
+impl fmt::Display for Integrator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Integrator::Euler => "euler",
+            Integrator::SemiImplicitEuler => "semi-implicit",
+            Integrator::Verlet => "verlet",
+            Integrator::Rk4 => "rk4",
+        }.fmt(f)
+    }
+}
+
+/// Prints how far each integrator drifts from a fine RK4 reference at different tick rates
+///
+/// The manoeuvre is ten seconds of full throttle in first gear while steering right.
+/// The forces don't depend on the tick rate, so the drift is down to the integrators and the other state stepped with them.
+pub fn print_report() {
+    const SECONDS: u32 = 10;
+    const RATES: [u32; 4] = [30, 60, 120, 240];
+    let controls = Controls {
+        throttle: 1.,
+        steering: 1.,
+        .. Default::default()
+    };
+    let run = |integrator: Integrator, tick_rate: u32| {
+        let mut car = Car::new(0., 0.);
+        car.gear = 1;
+        for _ in 0..SECONDS * tick_rate {
+            car.update(&controls, Surface::Asphalt, 1. / tick_rate as f32, integrator);
+        }
+        car.obj.pos
+    };
+
+    let reference = run(Integrator::Rk4, 2000);
+    println!("Position error in metres after {} s compared to rk4 at 2000 Hz", SECONDS);
+    print!("{:>14}", "");
+    for rate in &RATES {
+        print!("{:>9} Hz", rate);
+    }
+    println!();
+    for &integrator in &Integrator::ALL {
+        print!("{:>14}", integrator.to_string());
+        for &rate in &RATES {
+            let error = (run(integrator, rate) - reference).norm() / PIXELS_PER_METER;
+            print!("{:>12.3}", error);
+        }
+        println!();
+    }
+}
@@ -3,11 +3,21 @@ use ggez::graphics::{self, Point2, Vector2, Image};
 // use ggez::nalgebra as na;
 
 mod car;
+mod tyre;
+mod suspension;
+mod integrate;
+mod collision;
+mod controls;
 pub mod setup;
 
 pub use self::car::*;
+pub use self::tyre::*;
+pub use self::suspension::*;
+pub use self::integrate::*;
+pub use self::collision::*;
+pub use self::controls::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 /// A simple object that can be drawn to the screen
 pub struct Object {
     /// The position of the object
@@ -24,6 +34,13 @@ impl Object {
             rot: 0.,
         }
     }
+    /// Linearly interpolates between this and another object, `t` being how far towards `other` to go
+    pub fn lerp(&self, other: &Object, t: f32) -> Object {
+        Object {
+            pos: self.pos + t * (other.pos - self.pos),
+            rot: self.rot + t * (other.rot - self.rot),
+        }
+    }
     /// Draw the object
     pub fn draw(&self, ctx: &mut Context, img: &Image) -> GameResult<()> {
         let drawparams = graphics::DrawParam {
@@ -1,3 +1,5 @@
+use sim::Checksum;
+
 fn c_drag(coefficient: f32, area: f32) -> f32 {
     0.5 * coefficient * area * 1.29
 }
@@ -5,25 +7,197 @@ fn c_drag(coefficient: f32, area: f32) -> f32 {
 #[derive(Debug, Clone)]
 pub struct CarSetup {
     pub drag: f32,
+    pub aero: Aero,
     pub rolling_r: f32,
-    pub brake_force: f32,
+    pub brakes: Brakes,
+    pub tyres: TyreSetup,
     pub mass: f32,
     pub wheel_radius: f32,
     pub engine: Engine,
     pub transmission: Transmission,
     pub fw_dist: f32,
     pub rw_dist: f32,
+    /// Height of the centre of gravity in m
+    pub cg_height: f32,
+    /// Distance between the left and right wheels in m
+    pub track_width: f32,
+    pub suspension: SuspensionSetup,
 }
 
 const RAD_S_TO_RPM: f32 = 60. / (2. * ::std::f32::consts::PI);
+/// Goes first in `CarSetup::id`, bumped whenever what goes into the id changes
+const ID_VERSION: u8 = 1;
 
 impl CarSetup {
+    /// Reads a setup file as changes to the example setup, one `key value...` line per change:
+    ///
+    /// ```text
+    /// wing <degrees>            angle of the rear wing
+    /// bias <fraction>           fraction of the brake pressure going to the front axle
+    /// final-drive <ratio>       ratio of the final drive
+    /// gears <ratio>...          ratios of the forward gears from first to top
+    /// front-spring <N/m>        spring rate of each front wheel
+    /// rear-spring <N/m>         spring rate of each rear wheel
+    /// front-arb <N/m>           stiffness of the front anti-roll bar
+    /// rear-arb <N/m>            stiffness of the rear anti-roll bar
+    /// ```
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut setup = example();
+
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line: {}", line);
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap();
+            let values = words.map(|w| w.parse::<f32>()).collect::<Result<Vec<_>, _>>().map_err(|_| bad())?;
+            let positive = |v: f32| if v > 0. { Ok(v) } else { Err(format!("{} has to be positive: {}", key, line)) };
+            let not_negative = |v: f32| if v >= 0. { Ok(v) } else { Err(format!("{} can't be negative: {}", key, line)) };
+
+            match (key, &*values) {
+                ("wing", &[deg]) => {
+                    if !(deg >= 0. && deg <= setup.aero.max_wing_angle) {
+                        return Err(format!("The wing has to be from 0 to {} degrees: {}", setup.aero.max_wing_angle, line))
+                    }
+                    setup.aero.wing_angle = deg;
+                }
+                ("bias", &[f]) => {
+                    if !(f >= 0. && f <= 1.) {
+                        return Err(format!("The brake bias has to be from 0 to 1: {}", line))
+                    }
+                    setup.brakes.bias = f;
+                }
+                ("final-drive", &[r]) => setup.transmission.final_drive_ratio = positive(r)?,
+                ("gears", ratios) if !ratios.is_empty() && ratios.len() < 128 => {
+                    setup.transmission.ratios = ratios.iter().map(|&r| positive(r)).collect::<Result<_, _>>()?;
+                }
+                ("front-spring", &[k]) => setup.suspension.front_spring = positive(k)?,
+                ("rear-spring", &[k]) => setup.suspension.rear_spring = positive(k)?,
+                ("front-arb", &[k]) => setup.suspension.front_arb = not_negative(k)?,
+                ("rear-arb", &[k]) => setup.suspension.rear_arb = not_negative(k)?,
+                _ => return Err(bad()),
+            }
+        }
+        Ok(setup)
+    }
+
     pub fn get_engine_rpm(&self, speed: f32, gear: i8) -> f32 {
         let wheel_rot = speed / self.wheel_radius;
         let rpm = wheel_rot * self.transmission.get_gear_ratio(gear) * self.transmission.final_drive_ratio * RAD_S_TO_RPM;
         rpm.max(self.engine.idle_rpm)
     }
 
+    /// Fraction of the car's weight resting on the front axle
+    pub fn front_weight(&self) -> f32 {
+        self.rw_dist / (self.fw_dist + self.rw_dist)
+    }
+
+    /// Approximate moment of inertia of the body around its roll axis
+    pub fn roll_inertia(&self) -> f32 {
+        self.mass * (self.track_width * self.track_width + self.cg_height * self.cg_height) / 12.
+    }
+
+    /// Approximate moment of inertia of the body around its pitch axis
+    pub fn pitch_inertia(&self) -> f32 {
+        let wheelbase = self.fw_dist + self.rw_dist;
+        self.mass * (wheelbase * wheelbase + self.cg_height * self.cg_height) / 12.
+    }
+
+    /// Approximate moment of inertia of the body around its yaw axis
+    pub fn yaw_inertia(&self) -> f32 {
+        let wheelbase = self.fw_dist + self.rw_dist;
+        self.mass * (wheelbase * wheelbase + self.track_width * self.track_width) / 12.
+    }
+
+    /// Drag coefficient of the body and the wing together
+    pub fn drag_coefficient(&self) -> f32 {
+        self.drag + self.aero.wing_drag()
+    }
+
+    /// Normal forces on the front and rear axle in N at the given speed
+    pub fn axle_loads(&self, speed: f32) -> (f32, f32) {
+        let weight = self.mass * G;
+        let front = self.front_weight();
+        let (front_df, rear_df) = self.aero.downforce(speed);
+        (front * weight + front_df, (1. - front) * weight + rear_df)
+    }
+
+    /// The highest speed in m/s the car can reach on flat asphalt in any forward gear
+    pub fn top_speed(&self) -> f32 {
+        let mut top_speed = 0.;
+        for gear in 1..self.transmission.ratios.len() as i8 + 1 {
+            let mut speed = top_speed;
+            loop {
+                let rpm = self.get_engine_rpm(speed, gear);
+                let resistance = self.drag_coefficient() * speed * speed + self.rolling_r * speed;
+                if rpm > self.engine.redline_rpm || self.get_drive_force(rpm, gear, 1.) < resistance {
+                    break
+                }
+                speed += 0.1;
+            }
+            top_speed = speed.max(top_speed);
+        }
+        top_speed
+    }
+
+    /// The most lateral acceleration in g the tyres can give at the given speed
+    pub fn cornering_grip(&self, speed: f32) -> f32 {
+        let (front, rear) = self.axle_loads(speed);
+        self.tyres.friction * (front + rear) / (self.mass * G)
+    }
+
+    /// Identifies the setup, cars with the same id are set up exactly the same
+    ///
+    /// Ghosts and leaderboards are keyed on it, so the fields go in one by one in a fixed order.
+    pub fn id(&self) -> u64 {
+        let mut sum = Checksum::new();
+        sum.bytes(&[ID_VERSION]);
+        let (aero, brakes, tyres, susp) = (&self.aero, &self.brakes, &self.tyres, &self.suspension);
+        let (engine, torque, trans) = (&self.engine, &self.engine.torque, &self.transmission);
+        let values = [
+            self.drag, self.rolling_r, self.mass, self.wheel_radius, self.fw_dist, self.rw_dist, self.cg_height, self.track_width,
+            aero.front_downforce, aero.rear_downforce, aero.wing_downforce, aero.wing_drag, aero.wing_angle, aero.max_wing_angle,
+            brakes.front_torque, brakes.rear_torque, brakes.bias, brakes.heat_capacity, brakes.cooling, brakes.speed_cooling,
+            brakes.fade_start, brakes.fade_end, brakes.faded_friction,
+            tyres.friction, tyres.optimal_temp, tyres.temp_window, tyres.temp_sensitivity, tyres.load_sensitivity,
+            tyres.surface_capacity, tyres.core_capacity, tyres.conduction, tyres.cooling, tyres.speed_cooling,
+            tyres.rolling_heat, tyres.wear_rate, tyres.slip_wear, tyres.worn_grip_loss,
+            susp.front_spring, susp.rear_spring, susp.front_bump, susp.front_rebound, susp.rear_bump, susp.rear_rebound,
+            susp.front_arb, susp.rear_arb,
+            engine.idle_rpm, engine.redline_rpm, torque.a, torque.b, torque.c, torque.d,
+            trans.final_drive_ratio, trans.efficiency,
+        ];
+        for &v in values.iter() {
+            sum.f32(v);
+        }
+        // The number of gears goes in too so the ratios can't run into each other
+        for ratios in &[&trans.ratios, &trans.reverse_ratios] {
+            sum.bytes(&[ratios.len() as u8]);
+            for &ratio in ratios.iter() {
+                sum.f32(ratio);
+            }
+        }
+        sum.0
+    }
+
+    /// The engine speed at which changing up from `gear` gives more drive force, or `None` in the top gear
+    pub fn shift_rpm(&self, gear: i8) -> Option<f32> {
+        if gear < 1 || gear as usize >= self.transmission.ratios.len() {
+            return None
+        }
+        let ratio = self.transmission.get_gear_ratio(gear + 1) / self.transmission.get_gear_ratio(gear);
+        let mut rpm = self.engine.idle_rpm;
+        while rpm < self.engine.redline_rpm {
+            if self.get_drive_force(rpm * ratio, gear + 1, 1.) >= self.get_drive_force(rpm, gear, 1.) {
+                return Some(rpm)
+            }
+            rpm += 10.;
+        }
+        Some(self.engine.redline_rpm)
+    }
+
     pub fn get_drive_force(&self, rpm: f32, gear: i8, throttle: f32) -> f32 {
         let engine_torque = throttle * self.engine.get_torque(rpm);
         self.transmission.get_drive_torque(engine_torque, gear) / self.wheel_radius
@@ -35,9 +209,55 @@ pub fn example() -> CarSetup {
     CarSetup {
         fw_dist: 1.,
         rw_dist: 1.,
+        cg_height: 0.5,
+        track_width: 1.6,
+        suspension: SuspensionSetup {
+            front_spring: 35_000.,
+            rear_spring: 30_000.,
+            front_bump: 3000.,
+            front_rebound: 4500.,
+            rear_bump: 2800.,
+            rear_rebound: 4000.,
+            front_arb: 15_000.,
+            rear_arb: 8000.,
+        },
         drag,
+        aero: Aero {
+            front_downforce: c_drag(0.15, 2.2),
+            rear_downforce: c_drag(0.10, 2.2),
+            wing_downforce: c_drag(0.04, 1.),
+            wing_drag: c_drag(0.008, 1.),
+            wing_angle: 8.,
+            max_wing_angle: 20.,
+        },
         rolling_r: 30. * drag,
-        brake_force: 14_000.,
+        brakes: Brakes {
+            front_torque: 3200.,
+            rear_torque: 2400.,
+            bias: 0.6,
+            heat_capacity: 7400.,
+            cooling: 0.01,
+            speed_cooling: 0.002,
+            fade_start: 500.,
+            fade_end: 800.,
+            faded_friction: 0.4,
+        },
+        tyres: TyreSetup {
+            friction: 1.1,
+            optimal_temp: 90.,
+            temp_window: 40.,
+            temp_sensitivity: 0.3,
+            load_sensitivity: 0.1,
+            surface_capacity: 2000.,
+            core_capacity: 12000.,
+            conduction: 100.,
+            cooling: 5.,
+            speed_cooling: 1.5,
+            rolling_heat: 60.,
+            wear_rate: 4e-6,
+            slip_wear: 2e-7,
+            worn_grip_loss: 0.35,
+        },
         mass: 1500.,
         wheel_radius: 0.34,
         transmission: Transmission {
@@ -54,6 +274,149 @@ pub fn example() -> CarSetup {
     }
 }
 
+/// Temperature of the surroundings in °C
+pub const AMBIENT_TEMP: f32 = 20.;
+/// Gravitational acceleration in m/s²
+pub const G: f32 = 9.81;
+
+/// The aerodynamic coefficients are all on the form ½ρCA, so the force is the coefficient times the speed squared
+#[derive(Debug, Clone, Copy)]
+pub struct Aero {
+    /// Downforce of the body on the front axle
+    pub front_downforce: f32,
+    /// Downforce of the body on the rear axle
+    pub rear_downforce: f32,
+    /// Extra downforce on the rear axle per degree of wing angle
+    pub wing_downforce: f32,
+    /// Extra drag per degree of wing angle
+    pub wing_drag: f32,
+    /// Angle of attack of the rear wing in degrees
+    pub wing_angle: f32,
+    /// The steepest the wing can be set in degrees
+    pub max_wing_angle: f32,
+}
+
+impl Aero {
+    /// Downforce on the front and rear axle in N at the given speed
+    pub fn downforce(&self, speed: f32) -> (f32, f32) {
+        let v2 = speed * speed;
+        (self.front_downforce * v2, (self.rear_downforce + self.wing_downforce * self.wing_angle) * v2)
+    }
+    /// Drag coefficient added by the wing
+    pub fn wing_drag(&self) -> f32 {
+        self.wing_drag * self.wing_angle
+    }
+    /// Steepens the wing by `d` degrees
+    pub fn adjust_wing(&mut self, d: f32) {
+        self.wing_angle = (self.wing_angle + d).max(0.).min(self.max_wing_angle);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Brakes {
+    /// Maximum braking torque on the front axle in N m
+    pub front_torque: f32,
+    /// Maximum braking torque on the rear axle in N m
+    pub rear_torque: f32,
+    /// Fraction of the brake pressure going to the front axle
+    pub bias: f32,
+    /// Heat capacity of the discs on one axle in J/K
+    pub heat_capacity: f32,
+    /// Fraction of the excess heat lost per second when standing still
+    pub cooling: f32,
+    /// Extra cooling per m/s of speed
+    pub speed_cooling: f32,
+    /// Temperature in °C where the brakes start to fade
+    pub fade_start: f32,
+    /// Temperature in °C where the brakes are completely faded
+    pub fade_end: f32,
+    /// Friction multiplier of completely faded brakes
+    pub faded_friction: f32,
+}
+
+impl Brakes {
+    /// Gets the front and rear braking torques for the given pedal position
+    ///
+    /// The axle getting the bigger share of the pressure is at its full capacity when the pedal is fully pressed
+    pub fn get_torques(&self, pedal: f32) -> (f32, f32) {
+        let most = self.bias.max(1. - self.bias);
+        (pedal * self.front_torque * self.bias / most, pedal * self.rear_torque * (1. - self.bias) / most)
+    }
+    /// Friction multiplier of the discs at the given temperature
+    pub fn friction(&self, temp: f32) -> f32 {
+        let fade = ((temp - self.fade_start) / (self.fade_end - self.fade_start)).max(0.).min(1.);
+        1. - fade * (1. - self.faded_friction)
+    }
+    /// Gets the new temperature of an axle's discs after absorbing `energy` joules and cooling for `dt` seconds
+    pub fn heat(&self, temp: f32, energy: f32, speed: f32, dt: f32) -> f32 {
+        let temp = temp + energy / self.heat_capacity;
+        temp - (temp - AMBIENT_TEMP) * (self.cooling + self.speed_cooling * speed) * dt
+    }
+    /// Moves the bias towards the front by `d`
+    pub fn adjust_bias(&mut self, d: f32) {
+        self.bias = (self.bias + d).max(0.).min(1.);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SuspensionSetup {
+    /// Spring rate of each front wheel in N/m
+    pub front_spring: f32,
+    /// Spring rate of each rear wheel in N/m
+    pub rear_spring: f32,
+    /// Damping of each front wheel when compressing in N s/m
+    pub front_bump: f32,
+    /// Damping of each front wheel when extending in N s/m
+    pub front_rebound: f32,
+    /// Damping of each rear wheel when compressing in N s/m
+    pub rear_bump: f32,
+    /// Damping of each rear wheel when extending in N s/m
+    pub rear_rebound: f32,
+    /// Stiffness of the front anti-roll bar in N/m of difference in travel
+    pub front_arb: f32,
+    /// Stiffness of the rear anti-roll bar in N/m of difference in travel
+    pub rear_arb: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TyreSetup {
+    /// Friction coefficient of a new tyre at its optimal temperature
+    pub friction: f32,
+    /// Surface temperature in °C with the most grip
+    pub optimal_temp: f32,
+    /// How far in °C from the optimal temperature the grip has dropped by `temp_sensitivity`
+    pub temp_window: f32,
+    /// The most grip that can be lost from being out of the temperature window
+    pub temp_sensitivity: f32,
+    /// How much the friction drops per extra multiple of the static load on the tyre
+    pub load_sensitivity: f32,
+    /// Heat capacity of the tread surface in J/K
+    pub surface_capacity: f32,
+    /// Heat capacity of the carcass in J/K
+    pub core_capacity: f32,
+    /// Heat conducted between the surface and the carcass in W/K
+    pub conduction: f32,
+    /// Heat lost to the air when standing still in W/K
+    pub cooling: f32,
+    /// Extra heat lost to the air per m/s of speed in W/K
+    pub speed_cooling: f32,
+    /// Heat from the tyre flexing per metre rolled in J/m
+    pub rolling_heat: f32,
+    /// Wear per metre rolled
+    pub wear_rate: f32,
+    /// Wear per joule of slip energy
+    pub slip_wear: f32,
+    /// Fraction of the grip lost when the tyre is completely worn
+    pub worn_grip_loss: f32,
+}
+
+impl TyreSetup {
+    /// Friction coefficient of a tyre with the given load that has `friction` at its static load
+    pub fn load_friction(&self, friction: f32, load: f32, static_load: f32) -> f32 {
+        friction * (1. - self.load_sensitivity * (load / static_load - 1.))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Transmission {
     ratios: Vec<f32>,
@@ -70,6 +433,14 @@ impl Transmission {
             _ => -self.reverse_ratios[(-gear - 1) as usize],
         }
     }
+    /// Number of forward gears
+    pub fn gears(&self) -> i8 {
+        self.ratios.len() as i8
+    }
+    /// Number of reverse gears
+    pub fn reverse_gears(&self) -> i8 {
+        self.reverse_ratios.len() as i8
+    }
     pub fn get_drive_torque(&self, engine_torque: f32, gear: i8) -> f32 {
         engine_torque * self.get_gear_ratio(gear) * self.final_drive_ratio * self.efficiency
     }
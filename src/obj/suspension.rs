@@ -0,0 +1,81 @@
+use super::setup::CarSetup;
+
+#[derive(Debug, Clone, Copy, Default)]
+/// How the body of a car moves on its springs
+pub struct Suspension {
+    /// Roll angle in radians, positive when leaning onto the right wheels
+    pub roll: f32,
+    /// Roll speed in rad/s
+    pub roll_rate: f32,
+    /// Pitch angle in radians, positive when the nose dives
+    pub pitch: f32,
+    /// Pitch speed in rad/s
+    pub pitch_rate: f32,
+}
+
+impl Suspension {
+    /// How much each corner is compressed (front left, front right, rear left, rear right) for a given roll and pitch
+    fn travel(setup: &CarSetup, roll: f32, pitch: f32) -> [f32; 4] {
+        let side = 0.5 * setup.track_width * roll;
+        let (front, rear) = (pitch * setup.fw_dist, -pitch * setup.rw_dist);
+        [front - side, front + side, rear - side, rear + side]
+    }
+    /// The upwards force on the body from the springs, dampers and anti-roll bars at each corner in N
+    pub fn forces(&self, setup: &CarSetup) -> [f32; 4] {
+        let s = &setup.suspension;
+        let travel = Suspension::travel(setup, self.roll, self.pitch);
+        let speed = Suspension::travel(setup, self.roll_rate, self.pitch_rate);
+
+        let mut forces = [0.; 4];
+        for (i, force) in forces.iter_mut().enumerate() {
+            let (spring, bump, rebound) = if i < 2 {
+                (s.front_spring, s.front_bump, s.front_rebound)
+            } else {
+                (s.rear_spring, s.rear_bump, s.rear_rebound)
+            };
+            let damper = if speed[i] > 0. { bump } else { rebound };
+            *force = spring * travel[i] + damper * speed[i];
+        }
+        // The anti-roll bars push against the more compressed side and pull the other side down
+        let front_arb = s.front_arb * (travel[1] - travel[0]);
+        let rear_arb = s.rear_arb * (travel[3] - travel[2]);
+        forces[0] -= front_arb;
+        forces[1] += front_arb;
+        forces[2] -= rear_arb;
+        forces[3] += rear_arb;
+
+        forces
+    }
+    /// Normal force on each tyre in N at the given speed
+    pub fn corner_loads(&self, setup: &CarSetup, speed: f32) -> [f32; 4] {
+        let (front, rear) = setup.axle_loads(speed);
+        let forces = self.forces(setup);
+        let mut loads = [0.5 * front, 0.5 * front, 0.5 * rear, 0.5 * rear];
+        for (load, force) in loads.iter_mut().zip(forces.iter()) {
+            *load = (*load + force).max(0.);
+        }
+        // There's no heave motion, so the springs can only move load around
+        let sum = loads.iter().sum::<f32>();
+        if sum > 0. {
+            for load in &mut loads {
+                *load *= (front + rear) / sum;
+            }
+        }
+        loads
+    }
+    /// Lets the body react to the acceleration of the car for `dt` seconds
+    ///
+    /// `lateral` is positive when the car accelerates towards its left and `longitudinal` when it accelerates forwards
+    pub fn update(&mut self, setup: &CarSetup, lateral: f32, longitudinal: f32, dt: f32) {
+        let f = self.forces(setup);
+        let roll_moment = setup.mass * lateral * setup.cg_height
+            - 0.5 * setup.track_width * (f[1] + f[3] - f[0] - f[2]);
+        let pitch_moment = -setup.mass * longitudinal * setup.cg_height
+            - (setup.fw_dist * (f[0] + f[1]) - setup.rw_dist * (f[2] + f[3]));
+
+        self.roll_rate += roll_moment / setup.roll_inertia() * dt;
+        self.pitch_rate += pitch_moment / setup.pitch_inertia() * dt;
+        self.roll += self.roll_rate * dt;
+        self.pitch += self.pitch_rate * dt;
+    }
+}
@@ -0,0 +1,39 @@
+use super::setup::{TyreSetup, AMBIENT_TEMP};
+
+#[derive(Debug, Clone, Copy)]
+/// The thermal and wear state of a single tyre
+pub struct Tyre {
+    /// Temperature of the tread surface in °C
+    pub surface_temp: f32,
+    /// Temperature of the carcass in °C
+    pub core_temp: f32,
+    /// How worn the tyre is from `0` (new) to `1` (no tread left)
+    pub wear: f32,
+}
+
+impl Tyre {
+    /// A new tyre at ambient temperature
+    pub fn new() -> Self {
+        Tyre {
+            surface_temp: AMBIENT_TEMP,
+            core_temp: AMBIENT_TEMP,
+            wear: 0.,
+        }
+    }
+    /// Grip multiplier of the tyre given its temperature and wear
+    pub fn grip(&self, setup: &TyreSetup) -> f32 {
+        let off = (self.surface_temp - setup.optimal_temp) / setup.temp_window;
+        let temp_grip = (1. - setup.temp_sensitivity * off * off).max(1. - setup.temp_sensitivity);
+        temp_grip * (1. - self.wear * setup.worn_grip_loss)
+    }
+    /// Heats the tyre with `energy` joules of slip energy and lets it roll at `speed` for `dt` seconds
+    pub fn update(&mut self, setup: &TyreSetup, energy: f32, speed: f32, dt: f32) {
+        let conducted = setup.conduction * (self.surface_temp - self.core_temp) * dt;
+        let cooled = (setup.cooling + setup.speed_cooling * speed) * (self.surface_temp - AMBIENT_TEMP) * dt;
+        let rolling = setup.rolling_heat * speed * dt;
+
+        self.surface_temp += (energy + rolling - conducted - cooled) / setup.surface_capacity;
+        self.core_temp += conducted / setup.core_capacity;
+        self.wear = (self.wear + speed * dt * setup.wear_rate + energy * setup.slip_wear).min(1.);
+    }
+}
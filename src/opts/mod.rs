@@ -0,0 +1,244 @@
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use game::{Timestep, CameraMode, PadSettings, BINDINGS_FILE};
+use sim::replay::Replay;
+use sim::timing::Gate;
+use sim::ghost::Ghost;
+use sim::leaderboard::LEADERBOARD_FILE;
+use sim::ai::RacingLine;
+use hud::HudLayout;
+use world::WorldName;
+use obj::setup::CarSetup;
+
+#[derive(Debug, Default)]
+/// Settings given on the command line
+pub struct Options {
+    /// How the physics is stepped
+    pub timestep: Timestep,
+    /// Print how accurate the integrators are instead of playing
+    pub integrator_report: bool,
+    /// Seed for the randomness in the simulation
+    pub seed: u64,
+    /// Number of ticks in a recorded golden run
+    pub ticks: u64,
+    /// Record a golden run to this file instead of playing
+    pub record_golden: Option<String>,
+    /// Verify the golden runs in this file or directory instead of playing
+    pub verify_golden: Option<String>,
+    /// Record the session to this replay file
+    pub record: Option<String>,
+    /// Watch this replay instead of driving
+    pub replay: Option<Replay>,
+    /// Replays driving other cars to race against
+    pub opponents: Vec<Replay>,
+    /// Let the AI drive the car instead of the player
+    pub ai: bool,
+    /// Number of cars driven by the AI to race against
+    pub ai_opponents: u32,
+    /// The line the AI follows instead of the track's
+    pub racing_line: Option<RacingLine>,
+    /// Time this many laps of the AI driving instead of playing
+    pub benchmark: Option<usize>,
+    /// Work out the fastest line and lap time in theory instead of playing
+    pub lap_sim: bool,
+    /// Track limits file for the lap time simulation to use instead of the track
+    pub limits: Option<String>,
+    /// Setups for the benchmark and the lap time simulation to try, each with the file it's from
+    pub setups: Vec<(String, CarSetup)>,
+    /// Save the fastest line from the lap time simulation to this racing line file
+    pub save_line: Option<String>,
+    /// Log telemetry to this CSV file from the start
+    pub telemetry: Option<String>,
+    /// Send telemetry packets to this address every tick
+    pub udp: Option<SocketAddr>,
+    /// Print the telemetry packets received on this address instead of playing
+    pub udp_listen: Option<SocketAddr>,
+    /// The world to drive in
+    pub world: WorldName,
+    /// Start line to use instead of the track's
+    pub start_line: Option<Gate>,
+    /// Finish line to use instead of the track's
+    pub finish_line: Option<Gate>,
+    /// The leaderboard file runs are saved to
+    pub leaderboard: String,
+    /// Name of the driver on the leaderboard
+    pub driver: String,
+    /// Race this ghost file instead of the saved best lap
+    pub ghost: Option<Ghost>,
+    /// How the HUD starts out
+    pub hud: HudLayout,
+    /// What the camera starts out doing
+    pub camera: CameraMode,
+    /// Which gamepad axes do what
+    pub gamepad: PadSettings,
+    /// The file the key and button bindings are loaded from and saved to
+    pub bindings: String,
+}
+
+impl Options {
+    /// Reads the options from the command line arguments
+    ///
+    /// * `--tick-rate <hz>` sets how many physics ticks there are in a second
+    /// * `--substeps <n>` splits every tick into `n` smaller steps
+    /// * `--integrator <euler|semi-implicit|verlet|rk4>` chooses how the physics is integrated
+    /// * `--integrator-report` prints how accurate each integrator is at different tick rates and quits
+    /// * `--seed <n>` seeds the randomness in the simulation
+    /// * `--record-golden <file>` records a golden run of `--ticks <n>` ticks with the given seed and timestep and quits
+    /// * `--verify-golden <file or directory>` checks that golden runs still give the same checksums and quits
+    /// * `--record <file>` records everything the driver does to a replay file, without any opponents
+    /// * `--replay <file>` plays back a replay file, using its seed and timestep
+    /// * `--opponent <file>` adds a car driven by a replay file to race against, can be given more than once
+    /// * `--ai` lets the AI drive the car around the racing line
+    /// * `--ai-opponents <n>` adds `n` cars driven by the AI to race against, starting on the grid behind the player
+    /// * `--racing-line <file>` makes the AI follow a racing line file instead of `resources/tracks/<name>.line` or the centreline
+    /// * `--benchmark <laps>` prints the lap times of the AI driving each setup around the track and quits
+    /// * `--lap-sim` prints the theoretical lap time, the line length and the corner speeds of each setup on the fastest line around the track and quits
+    /// * `--limits <file>` makes the lap time simulation use a track limits file instead of the track, see `sim::laptime` for the format
+    /// * `--setup <file>` makes the benchmark and lap time simulation use a setup file instead of the example car,
+    ///   can be given more than once to compare setups, see `CarSetup::parse` for the format
+    /// * `--save-line <file>` saves the fastest line from the lap time simulation as a racing line file for the AI
+    /// * `--telemetry <file>` logs the car's state every tick to a CSV file
+    /// * `--udp <address:port>` sends telemetry packets to an address every tick, see `sim::udp` for the format
+    /// * `--udp-listen <address:port>` prints the telemetry packets received on an address and doesn't quit
+    /// * `--track <name>` drives on the track in `resources/tracks/<name>.trk`, the oval by default
+    /// * `--surface-map` drives on the surface map in `resources/surface.png` instead of a track
+    /// * `--start-line <x1,y1,x2,y2>` times laps from a line in metres instead of the track's start line, also on the surface map
+    /// * `--finish-line <x1,y1,x2,y2>` ends runs at a line in metres instead of back at the start line
+    /// * `--leaderboard <file>` saves runs to a leaderboard file instead of `leaderboard.txt`, the `leaderboard` command in `game::bindings` shows the top 10
+    /// * `--driver <name>` is the name runs are saved under on the leaderboard, the user's name by default
+    /// * `--ghost <file>` races a ghost file instead of the best lap saved in `ghosts/`
+    /// * `--camera <free|follow|chase|look-ahead>` chooses how the camera starts out, the `camera` command in `game::bindings` cycles through them while playing
+    /// * `--hud <text|gauges>` chooses whether the engine speed, speed and gear are shown as text or dials
+    /// * `--gamepad <file>` reads which gamepad, wheel and pedal axes do what from a file, see `game::gamepad` for the format
+    /// * `--bindings <file>` loads and saves what the keys and buttons do in a file instead of `bindings.txt`, see `game::bindings` for the format
+    pub fn from_args() -> Result<Self, String> {
+        let mut opts = Options {
+            ticks: 600,
+            leaderboard: LEADERBOARD_FILE.to_owned(),
+            bindings: BINDINGS_FILE.to_owned(),
+            driver: env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_else(|_| "driver".to_owned()),
+            .. Options::default()
+        };
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match &*arg {
+                "--tick-rate" => opts.timestep.tick_rate = value(&mut args, &arg)?,
+                "--substeps" => opts.timestep.substeps = value(&mut args, &arg)?,
+                "--integrator" => opts.timestep.integrator = value(&mut args, &arg)?,
+                "--integrator-report" => opts.integrator_report = true,
+                "--seed" => opts.seed = value(&mut args, &arg)?,
+                "--ticks" => opts.ticks = value(&mut args, &arg)?,
+                "--record-golden" => opts.record_golden = Some(value(&mut args, &arg)?),
+                "--verify-golden" => opts.verify_golden = Some(value(&mut args, &arg)?),
+                "--telemetry" => opts.telemetry = Some(value(&mut args, &arg)?),
+                "--udp" => opts.udp = Some(value(&mut args, &arg)?),
+                "--udp-listen" => opts.udp_listen = Some(value(&mut args, &arg)?),
+                "--track" => {
+                    let name: String = value(&mut args, &arg)?;
+                    // The name goes in files split on spaces, like the leaderboard
+                    if name.is_empty() || name.contains(char::is_whitespace) {
+                        return Err(format!("Track names have to be one word without spaces: {:?}", name));
+                    }
+                    opts.world = WorldName::Track(name);
+                }
+                "--surface-map" => opts.world = WorldName::SurfaceMap,
+                "--start-line" => opts.start_line = Some(value(&mut args, &arg)?),
+                "--finish-line" => opts.finish_line = Some(value(&mut args, &arg)?),
+                "--leaderboard" => opts.leaderboard = value(&mut args, &arg)?,
+                "--driver" => opts.driver = value(&mut args, &arg)?,
+                "--ghost" => {
+                    let path: String = value(&mut args, &arg)?;
+                    opts.ghost = Some(Ghost::load(&path).map_err(|e| format!("Couldn't load ghost {}: {}", path, e))?);
+                }
+                "--camera" => opts.camera = value(&mut args, &arg)?,
+                "--hud" => opts.hud = value(&mut args, &arg)?,
+                "--bindings" => opts.bindings = value(&mut args, &arg)?,
+                "--gamepad" => {
+                    let path: String = value(&mut args, &arg)?;
+                    let src = fs::read_to_string(&path).map_err(|e| format!("Couldn't load gamepad settings {}: {}", path, e))?;
+                    opts.gamepad = PadSettings::parse(&src).map_err(|e| format!("Couldn't load gamepad settings {}: {}", path, e))?;
+                }
+                "--record" => opts.record = Some(value(&mut args, &arg)?),
+                "--replay" => {
+                    let path: String = value(&mut args, &arg)?;
+                    let replay = Replay::load(&path).map_err(|e| format!("Couldn't load replay {}: {}", path, e))?;
+                    opts.seed = replay.seed;
+                    opts.timestep = replay.timestep;
+                    opts.world = replay.world.clone();
+                    opts.replay = Some(replay);
+                }
+                "--opponent" => {
+                    let path: String = value(&mut args, &arg)?;
+                    opts.opponents.push(Replay::load(&path).map_err(|e| format!("Couldn't load replay {}: {}", path, e))?);
+                }
+                "--ai" => opts.ai = true,
+                "--ai-opponents" => opts.ai_opponents = value(&mut args, &arg)?,
+                "--racing-line" => {
+                    let path: String = value(&mut args, &arg)?;
+                    let src = fs::read_to_string(&path).map_err(|e| format!("Couldn't load racing line {}: {}", path, e))?;
+                    opts.racing_line = Some(RacingLine::parse(&src).map_err(|e| format!("Couldn't load racing line {}: {}", path, e))?);
+                }
+                "--benchmark" => opts.benchmark = Some(value(&mut args, &arg)?),
+                "--lap-sim" => opts.lap_sim = true,
+                "--limits" => opts.limits = Some(value(&mut args, &arg)?),
+                "--setup" => {
+                    let path: String = value(&mut args, &arg)?;
+                    let src = fs::read_to_string(&path).map_err(|e| format!("Couldn't load setup {}: {}", path, e))?;
+                    let setup = CarSetup::parse(&src).map_err(|e| format!("Couldn't load setup {}: {}", path, e))?;
+                    opts.setups.push((path, setup));
+                }
+                "--save-line" => opts.save_line = Some(value(&mut args, &arg)?),
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+        if opts.timestep.tick_rate == 0 || opts.timestep.substeps == 0 {
+            return Err("The tick rate and substeps have to be positive".to_owned());
+        }
+        // The opponents can't drive the same if the world or the way the physics is stepped is different
+        for opponent in &opts.opponents {
+            if opponent.world != opts.world || opponent.timestep != opts.timestep {
+                let ts = opts.timestep;
+                return Err(format!("The opponents have to be recorded in {} at {} Hz with {} substeps integrated with {}",
+                    opts.world, ts.tick_rate, ts.substeps, ts.integrator));
+            }
+        }
+        // Only the driver goes in the replay, so cars they can run into would be missing when it's played back
+        if opts.record.is_some() && (!opts.opponents.is_empty() || opts.ai_opponents > 0) {
+            return Err("Replays can't be recorded with opponents".to_owned());
+        }
+        if opts.ai && opts.replay.is_some() {
+            return Err("The AI can't drive a replay".to_owned());
+        }
+        // Without a track there is no grid to start from or centreline to follow
+        if opts.world == WorldName::SurfaceMap {
+            if opts.lap_sim && opts.limits.is_none() {
+                return Err("The lap time simulation needs a track or track limits".to_owned());
+            }
+            if opts.ai_opponents > 0 || opts.benchmark.is_some() {
+                return Err("The AI opponents and benchmarks need a track".to_owned());
+            }
+            if opts.ai && opts.racing_line.is_none() {
+                return Err("The AI needs a racing line to drive on the surface map".to_owned());
+            }
+        }
+        if opts.save_line.is_some() && opts.setups.len() > 1 {
+            return Err("Only one setup's line can be saved".to_owned());
+        }
+        if opts.driver.trim().is_empty() {
+            return Err("The driver needs a name".to_owned());
+        }
+
+        Ok(opts)
+    }
+}
+
+/// Parses the value following a flag
+fn value<T: FromStr, I: Iterator<Item=String>>(args: &mut I, flag: &str) -> Result<T, String> {
+    args.next()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("{} needs a valid value", flag))
+}
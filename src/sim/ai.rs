@@ -0,0 +1,326 @@
+//! An AI driver that follows a racing line
+//!
+//! A racing line file has the waypoints of the line in driving order, all in metres:
+//!
+//! ```text
+//! point <x> <y>
+//! ```
+//!
+//! The line is closed, so the last point leads back to the first one.
+//! The driver steers by pure pursuit of a point on the line ahead of the car and
+//! keeps to a speed profile worked out from the curvature of the line and the grip of the car's setup.
+
+use ggez::{Context, GameResult, GameError};
+use ggez::graphics::{Point2, Vector2};
+
+use std::fs;
+use std::io::Read;
+use std::cmp::Ordering;
+use std::f32::consts::FRAC_PI_4;
+
+use super::{Sim, Action, TickInput, resources_dir, line};
+use super::timing::{Gates, LapTimer, format_ticks};
+use obj::{Car, Controls};
+use obj::setup::{CarSetup, G};
+use world::Track;
+use game::Timestep;
+use ::{PIXELS_PER_METER, angle_to_vec};
+
+/// Distance in m between the points a line is resampled to
+const SPACING: f32 = 1.;
+/// How many points on either side of a point the curvature there is measured over
+const CURVATURE_SPAN: usize = 6;
+/// Fraction of the tyres' grip the driver dares to use
+const GRIP_MARGIN: f32 = 0.85;
+/// How many seconds ahead of the car the point it steers towards is
+const LOOKAHEAD_TIME: f32 = 0.5;
+/// Limits to how far in m ahead of the car the point it steers towards is
+const LOOKAHEAD: (f32, f32) = (5., 30.);
+/// How many points past the last closest point the closest point is looked for
+const SEARCH: usize = 60;
+/// How far in m from the line the car can be before the whole line is searched for the closest point
+const LOST: f32 = 20.;
+/// How many seconds ahead the driver reacts to the target speed
+const REACTION_TIME: f32 = 0.2;
+/// How many m/s too fast the car can go before the driver brakes
+const BRAKE_MARGIN: f32 = 1.;
+/// How much the pedals are pressed per m/s the car is off the target speed
+const PEDAL_GAIN: f32 = 1.;
+/// How far in rpm below the redline the driver changes up at the latest
+const REDLINE_MARGIN: f32 = 100.;
+/// How far in rpm below the speed it would change up again at the engine has to be in the lower gear to change down
+const DOWNSHIFT_MARGIN: f32 = 1500.;
+/// How many seconds a benchmark lap can take before the car is taken to be stuck
+const BENCHMARK_TIMEOUT: u64 = 180;
+
+#[derive(Debug, Clone)]
+/// A closed line around a world for the AI to follow
+pub struct RacingLine {
+    /// Evenly spaced points on the line in pixels
+    points: Vec<Point2>,
+}
+
+impl RacingLine {
+    /// Loads the racing line of a track from `/tracks/<name>.line` in the resources, if it has one
+    pub fn load(ctx: &mut Context, track: &str) -> GameResult<Option<Self>> {
+        let path = format!("/tracks/{}.line", track);
+        if !ctx.filesystem.is_file(&path) {
+            return Ok(None)
+        }
+        let mut src = String::new();
+        ctx.filesystem.open(&path)?.read_to_string(&mut src)?;
+        RacingLine::parse(&src)
+            .map(Some)
+            .map_err(|e| GameError::ResourceLoadError(format!("Couldn't load racing line {}: {}", path, e)))
+    }
+    /// Reads a racing line from the contents of a racing line file
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut waypoints = Vec::new();
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line in racing line: {}", line);
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap();
+            let values = words.map(|v| v.parse::<f32>().map_err(|_| bad())).collect::<Result<Vec<_>, _>>()?;
+            match (key, &*values) {
+                ("point", &[x, y]) => waypoints.push(Point2::new(x, y) * PIXELS_PER_METER),
+                _ => return Err(bad()),
+            }
+        }
+        RacingLine::new(&waypoints)
+    }
+    /// The centreline of a track
+    pub fn centreline(track: &Track) -> Self {
+        let count = (track.length() / SPACING) as usize;
+        let waypoints: Vec<_> = (0..count).map(|i| track.point_at(i as f32 * SPACING).0).collect();
+        RacingLine::new(&waypoints).unwrap()
+    }
+    /// Makes a closed line through some waypoints in pixels
+    pub fn new(waypoints: &[Point2]) -> Result<Self, String> {
+        let n = waypoints.len();
+        if n < 3 {
+            return Err("A racing line needs at least 3 points".to_owned());
+        }
+        let points = line::resample(waypoints, SPACING * PIXELS_PER_METER).into_iter().map(|(i, t)| {
+            let (a, b) = (waypoints[i], waypoints[(i + 1) % n]);
+            a + t * (b - a)
+        }).collect();
+        Ok(RacingLine {
+            points,
+        })
+    }
+    /// The evenly spaced points on the line in pixels
+    pub fn points(&self) -> &[Point2] {
+        &self.points
+    }
+    /// Length of the line in m
+    pub fn length(&self) -> f32 {
+        let n = self.points.len();
+        (0..n).map(|i| (self.points[(i + 1) % n] - self.points[i]).norm()).sum::<f32>() / PIXELS_PER_METER
+    }
+    /// How sharply the line bends at each point in 1/m, by the circle through the points around it
+    pub fn curvatures(&self) -> Vec<f32> {
+        line::curvatures(&self.points, CURVATURE_SPAN).into_iter().map(|k| k * PIXELS_PER_METER).collect()
+    }
+    /// The fastest speed in m/s a car with `setup` can go at each point of the line
+    ///
+    /// Corners are taken as fast as the grip allows and the car brakes in time for them
+    pub fn speed_profile(&self, setup: &CarSetup) -> Vec<f32> {
+        let top_speed = setup.top_speed();
+        let mut speeds: Vec<f32> = self.curvatures().into_iter()
+            .map(|curvature| line::corner_speed(setup, curvature, GRIP_MARGIN, top_speed))
+            .collect();
+
+        let (front, rear) = setup.brakes.get_torques(1.);
+        let brake_decel = (front + rear) / setup.wheel_radius / setup.mass;
+        // Going backwards round the line twice so the end of it brakes for the start
+        let n = speeds.len();
+        for i in (0..2 * n).rev() {
+            let (j, next) = (i % n, (i + 1) % n);
+            let d = (self.points[next] - self.points[j]).norm() / PIXELS_PER_METER;
+            let decel = (GRIP_MARGIN * setup.cornering_grip(speeds[next]) * G).min(brake_decel);
+            speeds[j] = speeds[j].min((speeds[next] * speeds[next] + 2. * decel * d).sqrt());
+        }
+        speeds
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Drives a car around a racing line
+pub struct AiDriver {
+    line: RacingLine,
+    /// The speed to go at each point of the line
+    speeds: Vec<f32>,
+    /// Id of the setup the speeds were worked out for
+    setup: u64,
+    /// The point of the line closest to the car at the last tick
+    closest: Option<usize>,
+}
+
+impl AiDriver {
+    pub fn new(line: RacingLine) -> Self {
+        AiDriver {
+            line,
+            speeds: Vec::new(),
+            setup: 0,
+            closest: None,
+        }
+    }
+    pub fn line(&self) -> &RacingLine {
+        &self.line
+    }
+    /// The point of the line closest to `pos`, looking just ahead of the last one if the car is still near it
+    fn closest(&self, pos: Point2) -> usize {
+        let points = &self.line.points;
+        let n = points.len();
+        let dist = |&i: &usize| (points[i] - pos).norm_squared();
+        // A position that isn't a number is closest to the first point looked at
+        let nearest = |from: usize, count: usize| (from..from + count)
+            .map(|i| i % n)
+            .min_by(|a, b| dist(a).partial_cmp(&dist(b)).unwrap_or(Ordering::Equal))
+            .unwrap();
+        match self.closest {
+            Some(last) => {
+                let near = nearest(last, SEARCH.min(n));
+                if dist(&near) <= (LOST * PIXELS_PER_METER).powi(2) {
+                    near
+                } else {
+                    nearest(0, n)
+                }
+            }
+            None => nearest(0, n),
+        }
+    }
+    /// What the driver does with `car` this tick
+    pub fn drive(&mut self, car: &Car) -> TickInput {
+        let setup = &car.setup;
+        let id = setup.id();
+        if self.speeds.is_empty() || id != self.setup {
+            self.speeds = self.line.speed_profile(setup);
+            self.setup = id;
+        }
+        let closest = self.closest(car.obj.pos);
+        self.closest = Some(closest);
+        let points = &self.line.points;
+        let n = points.len();
+
+        let heading = angle_to_vec(car.obj.rot);
+        let speed = car.velocity.dot(&heading);
+        let wheelbase = setup.fw_dist + setup.rw_dist;
+
+        // Pure pursuit: steer the rear axle along the arc through the point a lookahead distance ahead
+        let rear = car.obj.pos - setup.rw_dist * PIXELS_PER_METER * heading;
+        let lookahead = (LOOKAHEAD_TIME * speed).max(LOOKAHEAD.0).min(LOOKAHEAD.1) * PIXELS_PER_METER;
+        let target = (1..n)
+            .map(|k| points[(closest + k) % n])
+            .find(|p| (p - rear).norm() >= lookahead)
+            .unwrap_or(points[closest]);
+        let to: Vector2 = (target - rear) / PIXELS_PER_METER;
+        let alpha = (heading.x * to.y - heading.y * to.x).atan2(heading.dot(&to));
+        let curvature = 2. * alpha.sin() / to.norm().max(1e-3);
+        let steer = (curvature * wheelbase).atan().max(-FRAC_PI_4).min(FRAC_PI_4);
+
+        // Hot or worn tyres don't grip like new ones, and the grip needed goes with the square of the speed
+        let tyre_grip = car.tyres.iter().map(|tyre| tyre.grip(&setup.tyres)).sum::<f32>() / 4.;
+        let ahead = (speed.max(0.) * REACTION_TIME / SPACING) as usize + 1;
+        let target_speed = tyre_grip.sqrt() * (0..ahead)
+            .map(|k| self.speeds[(closest + k) % n])
+            .fold(::std::f32::INFINITY, f32::min);
+        let controls = Controls {
+            throttle: PEDAL_GAIN * (target_speed - speed),
+            brake: PEDAL_GAIN * (speed - target_speed - BRAKE_MARGIN),
+            clutch: 0.,
+            steering: steer / FRAC_PI_4,
+            handbrake: 0.,
+        }.clamped();
+
+        let mut actions = Vec::new();
+        if car.gear < 1 {
+            actions.push(Action::Gear(1));
+        } else {
+            let change_up = setup.shift_rpm(car.gear)
+                .map(|rpm| car.engine_speed >= rpm.min(setup.engine.redline_rpm - REDLINE_MARGIN))
+                .unwrap_or(false);
+            if change_up {
+                actions.push(Action::Gear(car.gear + 1));
+            } else if let Some(rpm) = setup.shift_rpm(car.gear - 1) {
+                if setup.get_engine_rpm(speed, car.gear - 1) < rpm - DOWNSHIFT_MARGIN {
+                    actions.push(Action::Gear(car.gear - 1));
+                }
+            }
+        }
+
+        (controls, actions)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Laps driven by the AI without drawing anything, for comparing setups
+pub struct Benchmark {
+    /// Name of the track in `resources/tracks`
+    pub track: String,
+    pub laps: usize,
+    pub timestep: Timestep,
+    pub seed: u64,
+    /// The line to drive instead of the track's own
+    pub line: Option<RacingLine>,
+    /// The setup of the car
+    pub setup: CarSetup,
+}
+
+impl Benchmark {
+    /// Drives the laps from the first slot on the grid and prints the time of each
+    pub fn run(&self) -> Result<(), String> {
+        let read = |ext: &str| {
+            let path = resources_dir().join("tracks").join(format!("{}.{}", self.track, ext));
+            fs::read_to_string(&path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))
+        };
+        let track = Track::parse(&read("trk")?)?;
+        let gates = Gates::parse(&read("gates")?)?;
+        let line = match self.line {
+            Some(ref line) => line.clone(),
+            None => match read("line") {
+                Ok(src) => RacingLine::parse(&src)?,
+                Err(_) => RacingLine::centreline(&track),
+            },
+        };
+
+        let mut car = Car::new(0., 0.);
+        car.setup = self.setup.clone();
+        car.obj = track.grid(0);
+        println!("Setup {:016x} on {} ({:.0} m line), top speed {:.0} km/h",
+            car.setup.id(), track.name, line.length(), car.setup.top_speed() * 3.6);
+        let mut driver = AiDriver::new(line);
+        let mut timer = LapTimer::new(gates);
+        let mut sim = Sim::new(vec![car], track, self.timestep, self.seed);
+        let tick_rate = self.timestep.tick_rate;
+        let mut last_lap = 0;
+
+        while timer.laps.len() < self.laps {
+            if sim.tick - last_lap > BENCHMARK_TIMEOUT * tick_rate as u64 {
+                return Err(format!("Lap {} took more than {} s, the car is probably stuck", timer.lap_number(), BENCHMARK_TIMEOUT));
+            }
+            let from = sim.cars[0].obj.pos;
+            let controls = driver.drive(&sim.cars[0]);
+            sim.tick(&[controls]);
+            let line = match timer.update(from, sim.cars[0].obj.pos, sim.tick, Some(&sim.ground)) {
+                Some(lap) => {
+                    let sectors: Vec<_> = lap.sectors.iter().map(|&s| format_ticks(s, tick_rate)).collect();
+                    format!("{}  Sectors: {}{}", format_ticks(lap.ticks, tick_rate),
+                        sectors.join(" "), if lap.valid { "" } else { "  (invalid)" })
+                }
+                None => continue,
+            };
+            last_lap = sim.tick;
+            println!("Lap {}: {}", timer.laps.len(), line);
+        }
+        match timer.best() {
+            Some(best) => println!("Best: {}", format_ticks(best.ticks, tick_rate)),
+            None => println!("No valid laps"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Ghosts: where the car was at every tick of a lap, for racing against later
+//!
+//! A ghost file starts with a header of `key value` lines describing the lap,
+//! followed by a line of `---` and then the position in pixels and rotation of the car at every tick of the lap:
+//!
+//! ```text
+//! x y rot
+//! ```
+//!
+//! The player's best lap in each world with each car setup is saved in
+//! `ghosts/<world>-<setup id>.ghost`, so ghosts can be handed around and raced with `--ghost <file>`.
+
+use std::fs::{self, File};
+use std::io::{self, Write, BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use super::*;
+use obj::Object;
+use world::WorldName;
+
+/// Directory the player's ghosts are saved in
+pub const GHOST_DIR: &str = "ghosts";
+
+#[derive(Debug, Clone)]
+/// A recorded lap
+pub struct Ghost {
+    /// The world the lap was driven in
+    pub world: WorldName,
+    /// Id of the car setup the lap was driven with
+    pub setup: u64,
+    pub tick_rate: u32,
+    /// Where the car was at the start line and at every tick after it until it crossed it again
+    pub poses: Vec<Object>,
+}
+
+impl Ghost {
+    /// Where the player's best lap in `world` with the setup `setup` is saved
+    pub fn path(world: &WorldName, setup: u64) -> PathBuf {
+        Path::new(GHOST_DIR).join(format!("{}-{:016x}.ghost", world.file_name(), setup))
+    }
+    /// Length of the lap in ticks
+    pub fn lap_ticks(&self) -> u64 {
+        self.poses.len().saturating_sub(1) as u64
+    }
+    /// Length of the lap in seconds
+    pub fn lap_time(&self) -> f32 {
+        self.lap_ticks() as f32 / self.tick_rate as f32
+    }
+    /// Where the car was `time` seconds into the lap, or `None` if the lap was over by then
+    pub fn pose(&self, time: f32) -> Option<Object> {
+        let t = time * self.tick_rate as f32;
+        let i = t.floor() as usize;
+        match (self.poses.get(i), self.poses.get(i + 1)) {
+            (Some(a), Some(b)) => Some(a.lerp(b, t - i as f32)),
+            _ => None,
+        }
+    }
+    /// Saves the ghost, making the directory it goes in if needed
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# car-driver ghost")?;
+        writeln!(out, "{}", self.world)?;
+        writeln!(out, "setup {:016x}", self.setup)?;
+        writeln!(out, "tick-rate {}", self.tick_rate)?;
+        writeln!(out, "---")?;
+        for pose in &self.poses {
+            writeln!(out, "{} {} {}", pose.pos.x, pose.pos.y, pose.rot)?;
+        }
+        out.flush()
+    }
+    /// Loads a ghost file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut ghost = Ghost {
+            world: WorldName::SurfaceMap,
+            setup: 0,
+            tick_rate: 0,
+            poses: Vec::new(),
+        };
+        let mut in_header = true;
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line in ghost: {}", line);
+            let mut words = line.split_whitespace();
+            let first = words.next().unwrap();
+            let values: Vec<_> = words.collect();
+
+            if in_header {
+                match (first, &*values) {
+                    ("---", []) => in_header = false,
+                    ("track", [name]) => ghost.world = WorldName::Track(name.to_string()),
+                    ("surface-map", []) => ghost.world = WorldName::SurfaceMap,
+                    ("setup", [v]) => ghost.setup = u64::from_str_radix(v, 16).map_err(|_| bad())?,
+                    ("tick-rate", [v]) => ghost.tick_rate = v.parse().map_err(|_| bad())?,
+                    _ => return Err(bad()),
+                }
+                continue
+            }
+
+            let parse = |v: &str| v.parse::<f32>().map_err(|_| bad());
+            match (first, &*values) {
+                (x, [y, rot]) => ghost.poses.push(Object {
+                    pos: Point2::new(parse(x)?, parse(y)?),
+                    rot: parse(rot)?,
+                }),
+                _ => return Err(bad()),
+            }
+        }
+        if ghost.tick_rate == 0 {
+            return Err("The ghost needs a positive tick rate".to_owned());
+        }
+
+        Ok(ghost)
+    }
+}
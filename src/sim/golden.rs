@@ -0,0 +1,159 @@
+//! Golden runs: recorded checksums of every tick of a simulation driven by seeded random inputs
+//!
+//! A golden file starts with a header of `key value` lines followed by one hexadecimal checksum per tick.
+//! Lines starting with `#` are comments.
+//! Golden runs are only expected to match on the platform they were recorded on.
+
+use std::fs::{self, File};
+use std::io::{self, Write, BufRead, BufReader};
+use std::path::Path;
+
+use super::*;
+use obj::{Integrator, KeyboardRamp};
+use ext::InputState;
+
+#[derive(Debug, Clone, Copy)]
+/// The parameters of a golden run
+pub struct Run {
+    pub seed: u64,
+    pub ticks: u64,
+    pub timestep: Timestep,
+}
+
+impl Run {
+    /// Runs the simulation and gets the checksum of the car after each tick
+    ///
+    /// The keys held change randomly every half second and the gear every two seconds
+    pub fn checksums(&self) -> Vec<u64> {
+        let mut sim = Sim::new(vec![Car::new(0., 0.)], Surface::Asphalt, self.timestep, self.seed);
+        let mut input = InputState::default();
+        let mut keyboard = KeyboardRamp::default();
+        let ticks_per_second = self.timestep.tick_rate as u64;
+
+        (0..self.ticks).map(|tick| {
+            if tick % (ticks_per_second / 2).max(1) == 0 {
+                let mut key = || (sim.rng.next_f32() < 0.5) as u8;
+                input = InputState {
+                    up: key(),
+                    down: key(),
+                    left: key(),
+                    right: key(),
+                    handbrake: 0,
+                };
+            }
+            let mut actions = Vec::new();
+            if tick % (2 * ticks_per_second) == 0 {
+                actions.push(Action::Gear((sim.rng.next_u64() % 8) as i8 - 1));
+            }
+            let controls = keyboard.update(&input, self.timestep.tick());
+            sim.tick(&[(controls, actions)]);
+            sim.cars[0].checksum()
+        }).collect()
+    }
+    /// Records the run to a golden file
+    pub fn record<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "# Golden run, verify with --verify-golden")?;
+        writeln!(file, "seed {}", self.seed)?;
+        writeln!(file, "ticks {}", self.ticks)?;
+        writeln!(file, "tick-rate {}", self.timestep.tick_rate)?;
+        writeln!(file, "substeps {}", self.timestep.substeps)?;
+        writeln!(file, "integrator {}", self.timestep.integrator)?;
+        for checksum in self.checksums() {
+            writeln!(file, "{:016x}", checksum)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a golden file into its run parameters and the recorded checksums
+pub fn read<P: AsRef<Path>>(path: P) -> Result<(Run, Vec<u64>), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut run = Run {
+        seed: 0,
+        ticks: 0,
+        timestep: Timestep::default(),
+    };
+    let mut checksums = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let mut words = line.split_whitespace();
+        let (key, value) = (words.next().unwrap(), words.next());
+        let bad = || format!("Bad line in golden file: {}", line);
+        match (key, value) {
+            ("seed", Some(v)) => run.seed = v.parse().map_err(|_| bad())?,
+            ("ticks", Some(v)) => run.ticks = v.parse().map_err(|_| bad())?,
+            ("tick-rate", Some(v)) => run.timestep.tick_rate = v.parse().map_err(|_| bad())?,
+            ("substeps", Some(v)) => run.timestep.substeps = v.parse().map_err(|_| bad())?,
+            ("integrator", Some(v)) => run.timestep.integrator = v.parse::<Integrator>()?,
+            (checksum, None) => checksums.push(u64::from_str_radix(checksum, 16).map_err(|_| bad())?),
+            _ => return Err(bad()),
+        }
+    }
+    if checksums.len() as u64 != run.ticks {
+        return Err(format!("Expected {} checksums but found {}", run.ticks, checksums.len()));
+    }
+
+    Ok((run, checksums))
+}
+
+/// Verifies a golden file, or every golden file in a directory, printing the result of each
+///
+/// Returns whether they all matched
+pub fn verify<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    if path.is_dir() {
+        let mut paths: Vec<_> = match fs::read_dir(path) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                return false
+            }
+        };
+        paths.sort();
+        // Check all of them instead of stopping at the first failure
+        return paths.iter().map(|p| verify(p)).fold(true, |a, b| a && b);
+    }
+
+    let (run, expected) = match read(path) {
+        Ok(golden) => golden,
+        Err(e) => {
+            println!("{}: {}", path.display(), e);
+            return false
+        }
+    };
+    let mismatch = run.checksums().iter().zip(expected.iter()).position(|(a, b)| a != b);
+    match mismatch {
+        None => {
+            println!("{}: ok ({} ticks)", path.display(), run.ticks);
+            true
+        }
+        Some(tick) => {
+            println!("{}: diverged at tick {}", path.display(), tick);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn golden_runs_match() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("golden");
+        let mut paths: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "no golden runs in {}", dir.display());
+        for path in paths {
+            assert!(verify(&path), "{} doesn't match", path.display());
+        }
+    }
+}
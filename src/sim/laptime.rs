@@ -0,0 +1,352 @@
+//! Quasi-steady-state lap time simulation: how fast a car could get around a track in theory
+//!
+//! The track is a closed centreline with how far the road goes to the left and right of it.
+//! A track limits file has a line per point of the centreline in driving order, all in metres:
+//!
+//! ```text
+//! point <x> <y> <left> <right>
+//! ```
+//!
+//! Track files can be used too, with the road as wide on both sides of the centreline.
+//!
+//! The racing line is first found as the line with the least curvature within the limits
+//! and then nudged wherever that makes the lap faster. Along the line the car takes every corner
+//! as fast as its grip allows, accelerates as hard as the engine and the grip left over from cornering allow,
+//! and brakes as late as it can, as if it settled at every point (the quasi-steady-state assumption).
+
+use std::fs::File;
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+use std::f32::consts::PI;
+
+use ggez::graphics::{Point2, Vector2};
+
+use obj::setup::{CarSetup, G};
+use world::Track;
+use super::timing::format_ticks;
+use super::line;
+use ::PIXELS_PER_METER;
+
+/// Distance in m between the points of the track
+const SPACING: f32 = 2.;
+/// How far in m the middle of the car keeps from the track limits
+const MARGIN: f32 = 1.;
+/// Strides between the points moved at each level of the least curvature search, coarsest first
+const STRIDES: [usize; 5] = [16, 8, 4, 2, 1];
+/// How many times the points are swept over at each level of the search
+const SWEEPS: usize = 300;
+/// How many points on either side of a nudge the line moves with it
+const NUDGE_SPAN: usize = 10;
+/// Sizes in m of the nudges tried, largest first
+const NUDGES: [f32; 3] = [0.5, 0.2, 0.1];
+/// The most passes over the line with each size of nudge
+const NUDGE_PASSES: usize = 4;
+/// How many m on either side of a point it has to be slowest to be a corner
+const CORNER_WINDOW: f32 = 40.;
+/// Fraction of the fastest speed on the lap a corner has to be slower than
+const CORNER_SPEED: f32 = 0.97;
+
+#[derive(Debug, Clone)]
+/// Where the road of a track is
+pub struct Limits {
+    pub name: String,
+    /// Evenly spaced points on the centreline in m
+    centre: Vec<Point2>,
+    /// Unit vectors pointing to the right of the driving direction at each point
+    normals: Vec<Vector2>,
+    /// How far the road goes to the left and right of each point in m
+    widths: Vec<(f32, f32)>,
+}
+
+impl Limits {
+    /// Reads the contents of a track limits file
+    pub fn parse(name: &str, src: &str) -> Result<Self, String> {
+        let mut points = Vec::new();
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line in track limits: {}", line);
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap();
+            let values = words.map(|v| v.parse::<f32>().map_err(|_| bad())).collect::<Result<Vec<_>, _>>()?;
+            match (key, &*values) {
+                ("point", &[x, y, left, right]) if left >= 0. && right >= 0. => points.push((Point2::new(x, y), left, right)),
+                _ => return Err(bad()),
+            }
+        }
+        Limits::new(name, &points)
+    }
+    /// The road of a track
+    pub fn from_track(track: &Track) -> Self {
+        let count = (track.length() / SPACING).round() as usize;
+        let points: Vec<_> = (0..count).map(|i| {
+            let (pos, _) = track.point_at(i as f32 * track.length() / count as f32);
+            let half = 0.5 * track.project(pos).width;
+            (pos / PIXELS_PER_METER, half, half)
+        }).collect();
+        Limits::new(&track.name, &points).unwrap()
+    }
+    /// Makes the limits from points on the centreline in m and the widths to the left and right of them
+    fn new(name: &str, points: &[(Point2, f32, f32)]) -> Result<Self, String> {
+        let n = points.len();
+        if n < 3 {
+            return Err("The track limits need at least 3 points".to_owned());
+        }
+        let centres: Vec<Point2> = points.iter().map(|p| p.0).collect();
+        let stations = line::resample(&centres, SPACING);
+        let count = stations.len();
+
+        let mut centre = Vec::with_capacity(count);
+        let mut widths = Vec::with_capacity(count);
+        for (i, t) in stations {
+            let ((a, a_left, a_right), (b, b_left, b_right)) = (points[i], points[(i + 1) % n]);
+            centre.push(a + t * (b - a));
+            widths.push((a_left + t * (b_left - a_left), a_right + t * (b_right - a_right)));
+        }
+        let normals = (0..count).map(|i| {
+            let dir = (centre[(i + 1) % count] - centre[(i + count - 1) % count]).normalize();
+            Vector2::new(-dir.y, dir.x)
+        }).collect();
+
+        Ok(Limits {
+            name: name.to_owned(),
+            centre,
+            normals,
+            widths,
+        })
+    }
+    /// Length of the centreline in m
+    pub fn length(&self) -> f32 {
+        let n = self.centre.len();
+        (0..n).map(|i| (self.centre[(i + 1) % n] - self.centre[i]).norm()).sum()
+    }
+    /// The line going `offsets` m to the right of each point of the centreline
+    pub fn line(&self, offsets: &[f32]) -> Vec<Point2> {
+        self.centre.iter().zip(&self.normals).zip(offsets)
+            .map(|((&centre, &normal), &offset)| centre + offset * normal)
+            .collect()
+    }
+    /// The closest offset to `offset` at the `i`th point that keeps the car on the road
+    fn clamp(&self, i: usize, offset: f32) -> f32 {
+        let (left, right) = self.widths[i];
+        // On a road too narrow for the margins the car can still drive down the middle
+        offset.max((MARGIN - left).min(0.)).min((right - MARGIN).max(0.))
+    }
+    /// The offsets of the line with the least curvature that stays on the road
+    ///
+    /// Every point in turn is moved across the road to where it bends the line the least,
+    /// first with only a few points far apart and then with more and more points so the search doesn't take forever.
+    pub fn least_curvature(&self) -> Vec<f32> {
+        let n = self.centre.len();
+        let mut offsets = vec![0.; n];
+        let point = |offsets: &[f32], j: usize| self.centre[j] + offsets[j] * self.normals[j];
+
+        for &stride in STRIDES.iter().filter(|&&stride| n >= 8 * stride) {
+            for _ in 0..SWEEPS {
+                for i in (0..n).step_by(stride) {
+                    let at = |k: usize| point(&offsets, (i + 2 * n + k * stride - 2 * stride) % n).coords;
+                    // Where the point makes the second differences around it smallest
+                    let best = (4. * (at(1) + at(3)) - at(0) - at(4)) / 6.;
+                    offsets[i] = self.clamp(i, (best - self.centre[i].coords).dot(&self.normals[i]));
+                }
+            }
+            // Fill in the points in between for the next level
+            for i in (0..n).filter(|i| i % stride != 0) {
+                let a = i - i % stride;
+                let gap = if a + stride < n { stride } else { n - a };
+                let t = (i - a) as f32 / gap as f32;
+                offsets[i] = self.clamp(i, (1. - t) * offsets[a] + t * offsets[(a + gap) % n]);
+            }
+        }
+        offsets
+    }
+}
+
+#[derive(Debug, Clone)]
+/// How fast a car goes around a line
+pub struct Profile {
+    /// Speed in m/s at each point
+    pub speeds: Vec<f32>,
+    /// Time in s to go around the line once
+    pub lap_time: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// The slowest point of a corner
+pub struct Corner {
+    /// Distance along the centreline from its first point in m
+    pub distance: f32,
+    /// Speed in m/s
+    pub speed: f32,
+    pub gear: i8,
+}
+
+/// Works out how fast a car with some setup can go around lines
+pub struct LapSim {
+    pub setup: CarSetup,
+    top_speed: f32,
+    /// Deceleration in m/s² the brakes can give by themselves
+    brake_decel: f32,
+}
+
+impl LapSim {
+    pub fn new(setup: CarSetup) -> Self {
+        let (front, rear) = setup.brakes.get_torques(1.);
+        LapSim {
+            top_speed: setup.top_speed(),
+            brake_decel: (front + rear) / setup.wheel_radius / setup.mass,
+            setup,
+        }
+    }
+    /// The forward gear with the most drive force at `speed` without going over the redline, and that force in N
+    pub fn best_gear(&self, speed: f32) -> (i8, f32) {
+        let setup = &self.setup;
+        (1..setup.transmission.gears() + 1)
+            .map(|gear| {
+                let rpm = setup.get_engine_rpm(speed, gear);
+                let force = if rpm <= setup.engine.redline_rpm { setup.get_drive_force(rpm, gear, 1.) } else { 0. };
+                (gear, force)
+            })
+            .fold((setup.transmission.gears(), 0.), |best, gear| if gear.1 > best.1 { gear } else { best })
+    }
+    /// Deceleration in m/s² from the drag and rolling resistance at `speed`
+    fn resistance(&self, speed: f32) -> f32 {
+        (self.setup.drag_coefficient() * speed * speed + self.setup.rolling_r * speed) / self.setup.mass
+    }
+    /// The acceleration in m/s² the tyres have left for speeding up or braking when cornering
+    fn grip_left(&self, speed: f32, curvature: f32) -> f32 {
+        let most = self.setup.cornering_grip(speed) * G;
+        let lateral = speed * speed * curvature;
+        most * (1. - (lateral / most).powi(2)).max(0.).sqrt()
+    }
+    /// How fast the car goes around a closed line of points in m
+    pub fn profile(&self, line: &[Point2]) -> Profile {
+        let n = line.len();
+        let curvatures = line::curvatures(line, 1);
+        let distances: Vec<f32> = (0..n).map(|i| (line[(i + 1) % n] - line[i]).norm()).collect();
+        // In theory the car uses all of its grip
+        let limits: Vec<f32> = curvatures.iter().map(|&k| line::corner_speed(&self.setup, k, 1., self.top_speed)).collect();
+
+        // Accelerating out of every corner and braking into every corner, twice around so the line's end meets its start
+        let mut accelerating = limits.clone();
+        for i in 0..2 * n {
+            let (j, next) = (i % n, (i + 1) % n);
+            let v = accelerating[j];
+            let acc = (self.best_gear(v).1 / self.setup.mass - self.resistance(v)).min(self.grip_left(v, curvatures[j]));
+            accelerating[next] = accelerating[next].min((v * v + 2. * acc.max(0.) * distances[j]).sqrt());
+        }
+        let mut braking = limits;
+        for i in (0..2 * n).rev() {
+            let (j, next) = (i % n, (i + 1) % n);
+            let v = braking[next];
+            let decel = self.brake_decel.min(self.grip_left(v, curvatures[next])) + self.resistance(v);
+            braking[j] = braking[j].min((v * v + 2. * decel * distances[j]).sqrt());
+        }
+
+        let speeds: Vec<f32> = accelerating.iter().zip(&braking).map(|(a, b)| a.min(*b)).collect();
+        let lap_time = (0..n).map(|i| 2. * distances[i] / (speeds[i] + speeds[(i + 1) % n]).max(1e-3)).sum();
+        Profile {
+            speeds,
+            lap_time,
+        }
+    }
+    /// The offsets from the centreline of the fastest line found within `limits`
+    ///
+    /// Starts from the line of least curvature and keeps nudging parts of it sideways as long as that's faster
+    pub fn optimise(&self, limits: &Limits) -> Vec<f32> {
+        let mut offsets = limits.least_curvature();
+        let n = offsets.len();
+        if n <= 2 * NUDGE_SPAN {
+            return offsets
+        }
+        let mut best = self.profile(&limits.line(&offsets)).lap_time;
+
+        for &size in &NUDGES {
+            for _ in 0..NUDGE_PASSES {
+                let mut faster = false;
+                for centre in (0..n).step_by(NUDGE_SPAN / 2) {
+                    for &side in &[1., -1.] {
+                        // A smooth bump so the nudge doesn't put kinks in the line
+                        let mut tried = offsets.clone();
+                        for k in 0..2 * NUDGE_SPAN + 1 {
+                            let j = (centre + n + k - NUDGE_SPAN) % n;
+                            let x = (k as f32 - NUDGE_SPAN as f32) / (NUDGE_SPAN + 1) as f32;
+                            tried[j] = limits.clamp(j, tried[j] + side * size * 0.5 * (1. + (PI * x).cos()));
+                        }
+                        let time = self.profile(&limits.line(&tried)).lap_time;
+                        if time < best {
+                            best = time;
+                            offsets = tried;
+                            faster = true;
+                            break
+                        }
+                    }
+                }
+                if !faster {
+                    break
+                }
+            }
+        }
+        offsets
+    }
+    /// The slowest point of each corner of a profile of a line with `spacing` m between its points
+    pub fn corners(&self, profile: &Profile, spacing: f32) -> Vec<Corner> {
+        let speeds = &profile.speeds;
+        let n = speeds.len();
+        let window = ((CORNER_WINDOW / spacing) as usize).min((n - 1) / 2);
+        let fastest = speeds.iter().cloned().fold(0., f32::max);
+
+        (0..n).filter(|&i| {
+            let v = speeds[i];
+            // Strictly slower than the points before it so a flat bottom is only counted once
+            v < CORNER_SPEED * fastest
+                && (1..window + 1).all(|k| v < speeds[(i + n - k) % n] && v <= speeds[(i + k) % n])
+        }).map(|i| Corner {
+            distance: i as f32 * spacing,
+            speed: speeds[i],
+            gear: self.best_gear(speeds[i]).0,
+        }).collect()
+    }
+}
+
+/// Finds the fastest line around a track for a setup, prints the lap time and corner speeds
+/// and saves the line as a racing line file if `save_line` is given
+pub fn analyse(limits: &Limits, setup: CarSetup, save_line: Option<&str>) -> Result<(), String> {
+    let sim = LapSim::new(setup);
+    let centreline = limits.length();
+    let spacing = centreline / limits.centre.len() as f32;
+
+    let least_curvature = sim.profile(&limits.line(&limits.least_curvature()));
+    let offsets = sim.optimise(limits);
+    let line = limits.line(&offsets);
+    let profile = sim.profile(&line);
+    let length: f32 = (0..line.len()).map(|i| (line[(i + 1) % line.len()] - line[i]).norm()).sum();
+    let millis = |time: f32| (time * 1000.).round() as u64;
+
+    println!("Setup {:016x} on {} ({:.0} m centreline), top speed {:.0} km/h",
+        sim.setup.id(), limits.name, centreline, sim.top_speed * 3.6);
+    println!("Least curvature line: {}", format_ticks(millis(least_curvature.lap_time), 1000));
+    println!("Optimised line: {} ({:.0} m)", format_ticks(millis(profile.lap_time), 1000), length);
+    println!("Corner  Distance  Min speed  Gear");
+    for (i, corner) in sim.corners(&profile, spacing).iter().enumerate() {
+        println!("{:>6}  {:>6.0} m  {:>4.0} km/h  {:>4}", i + 1, corner.distance, corner.speed * 3.6, corner.gear);
+    }
+
+    if let Some(path) = save_line {
+        save(path, limits, &sim, &line, profile.lap_time).map_err(|e| format!("Couldn't save the racing line to {}: {}", path, e))?;
+        println!("Saved the racing line to {}", path);
+    }
+    Ok(())
+}
+
+/// Saves a line of points in m as a racing line file for the AI to follow
+fn save<P: AsRef<Path>>(path: P, limits: &Limits, sim: &LapSim, line: &[Point2], lap_time: f32) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "# Racing line of {} for setup {:016x}, {:.3} s in theory", limits.name, sim.setup.id(), lap_time)?;
+    for p in line {
+        writeln!(out, "point {:.2} {:.2}", p.x, p.y)?;
+    }
+    out.flush()
+}
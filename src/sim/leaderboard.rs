@@ -0,0 +1,126 @@
+//! Leaderboards of the fastest runs in each world with each car setup
+//!
+//! Every valid run is added to the end of the leaderboard file as a line of
+//!
+//! ```text
+//! <world> <setup id> <time in ms> <date> <driver>
+//! ```
+//!
+//! where the setup id is the hexadecimal `CarSetup::id` and the date is in UTC like `2018-06-01T12:00:00Z`.
+//! Runs only compete with runs in the same world with exactly the same setup, so tuned cars don't mix with stock ones.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use world::WorldName;
+
+/// The leaderboard file used unless another one is given
+pub const LEADERBOARD_FILE: &str = "leaderboard.txt";
+/// How many runs a leaderboard shows
+pub const TOP: usize = 10;
+
+#[derive(Debug, Clone)]
+/// A run on the leaderboard
+pub struct Entry {
+    pub world: String,
+    pub setup: u64,
+    pub millis: u64,
+    pub date: String,
+    pub driver: String,
+}
+
+#[derive(Debug)]
+/// All the runs in a leaderboard file
+pub struct Leaderboard {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    /// Loads a leaderboard file, which doesn't have to exist yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref().to_owned();
+        let mut entries = Vec::new();
+        if path.exists() {
+            let file = File::open(&path).map_err(|e| e.to_string())?;
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| e.to_string())?;
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue
+                }
+                let bad = || format!("Bad line in leaderboard: {}", line);
+                let words: Vec<_> = line.splitn(5, ' ').collect();
+                match *words {
+                    [world, setup, millis, date, driver] => entries.push(Entry {
+                        world: world.to_owned(),
+                        setup: u64::from_str_radix(setup, 16).map_err(|_| bad())?,
+                        millis: millis.parse().map_err(|_| bad())?,
+                        date: date.to_owned(),
+                        driver: driver.to_owned(),
+                    }),
+                    _ => return Err(bad()),
+                }
+            }
+        }
+        Ok(Leaderboard {
+            path,
+            entries,
+        })
+    }
+    /// Adds a run by `driver` finished now and saves it to the file
+    ///
+    /// Returns the run's place on the leaderboard counting from 1
+    pub fn add(&mut self, world: &WorldName, setup: u64, millis: u64, driver: &str) -> io::Result<usize> {
+        // Spaces would run into the next column
+        if world.file_name().contains(char::is_whitespace) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The world name {:?} has spaces", world.file_name())));
+        }
+        let entry = Entry {
+            world: world.file_name().to_owned(),
+            setup,
+            millis,
+            date: utc_now(),
+            driver: driver.trim().to_owned(),
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{} {:016x} {} {} {}", entry.world, entry.setup, entry.millis, entry.date, entry.driver)?;
+        self.entries.push(entry);
+
+        // Earlier runs with the same time stay ahead
+        Ok(self.entries.iter()
+            .filter(|e| e.world == world.file_name() && e.setup == setup && e.millis <= millis)
+            .count())
+    }
+    /// The fastest `TOP` runs in `world` with the setup `setup`, fastest first
+    pub fn top(&self, world: &WorldName, setup: u64) -> Vec<&Entry> {
+        let mut runs: Vec<_> = self.entries.iter()
+            .filter(|e| e.world == world.file_name() && e.setup == setup)
+            .collect();
+        // Ties go to whoever got there first
+        runs.sort_by_key(|e| e.millis);
+        runs.truncate(TOP);
+        runs
+    }
+}
+
+/// The current date and time in UTC like `2018-06-01T12:00:00Z`
+fn utc_now() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Converts days since 1970-01-01 to a date in the proleptic Gregorian calendar
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
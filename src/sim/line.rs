@@ -0,0 +1,62 @@
+//! Closed lines around a track, shared by the AI's racing lines and the lap time simulation
+//!
+//! Both put down evenly spaced points along a line, measure how sharply it bends
+//! and work out how fast the car can take each bend, so they do it the same way.
+
+use ggez::graphics::Point2;
+
+use obj::setup::{CarSetup, G};
+
+/// How many times the corner speed is put back into the grip it needs
+const CORNER_ITERATIONS: usize = 10;
+
+/// Where evenly spaced points on a closed line through `points` are, about `spacing` apart
+///
+/// Each point is given as the index of the segment from `points[i]` to the point after it and how far along it is from 0 to 1.
+pub fn resample(points: &[Point2], spacing: f32) -> Vec<(usize, f32)> {
+    let n = points.len();
+    let lengths: Vec<f32> = (0..n).map(|i| (points[(i + 1) % n] - points[i]).norm()).collect();
+    let length: f32 = lengths.iter().sum();
+    let count = ((length / spacing).round() as usize).max(3);
+    let step = length / count as f32;
+
+    // Walk along the segments putting down a point every step
+    let mut stations = Vec::with_capacity(count);
+    let (mut i, mut start) = (0, 0.);
+    for k in 0..count {
+        let d = k as f32 * step;
+        while i < n - 1 && start + lengths[i] < d {
+            start += lengths[i];
+            i += 1;
+        }
+        let t = if lengths[i] > 0. { ((d - start) / lengths[i]).min(1.) } else { 0. };
+        stations.push((i, t));
+    }
+    stations
+}
+
+/// How sharply a closed line bends at each point, by the circle through it and the points `span` points away on either side
+///
+/// The curvature is in one over the unit of the points.
+pub fn curvatures(points: &[Point2], span: usize) -> Vec<f32> {
+    let n = points.len();
+    let span = span.min((n - 1) / 2);
+    (0..n).map(|i| {
+        let (a, b, c) = (points[(i + n - span) % n], points[i], points[(i + span) % n]);
+        let (ab, bc, ca) = (b - a, c - b, a - c);
+        let cross = ab.x * bc.y - ab.y * bc.x;
+        let lengths = ab.norm() * bc.norm() * ca.norm();
+        if lengths > 0. { 2. * cross.abs() / lengths } else { 0. }
+    }).collect()
+}
+
+/// The fastest speed in m/s a car with `setup` can hold around a bend with a curvature in 1/m
+/// using the fraction `grip` of its tyres' grip and going no faster than `top_speed`
+pub fn corner_speed(setup: &CarSetup, curvature: f32, grip: f32, top_speed: f32) -> f32 {
+    // The grip grows with the downforce, so settle on a speed the grip at that speed can hold
+    let mut speed = top_speed;
+    for _ in 0..CORNER_ITERATIONS {
+        speed = (grip * setup.cornering_grip(speed) * G / curvature.max(1e-6)).sqrt().min(top_speed);
+    }
+    speed
+}
@@ -0,0 +1,211 @@
+use ggez::graphics::Point2;
+
+use std::env;
+use std::path::PathBuf;
+
+use obj::{Car, Controls, Collider, Material, resolve};
+use world::{Surface, SurfaceMap, Track, World};
+use game::Timestep;
+
+pub mod ai;
+pub mod ghost;
+pub mod golden;
+pub mod laptime;
+pub mod leaderboard;
+pub mod line;
+pub mod replay;
+pub mod telemetry;
+pub mod timing;
+pub mod udp;
+
+/// Anything that can tell what surface is at a point in the world
+pub trait Ground {
+    /// The surface at `p`
+    fn surface(&self, p: Point2) -> Surface;
+    /// The walls and obstacles cars can hit
+    fn colliders(&self) -> &[Collider] {
+        &[]
+    }
+}
+
+impl Ground for Surface {
+    fn surface(&self, _: Point2) -> Surface {
+        *self
+    }
+}
+
+impl Ground for SurfaceMap {
+    fn surface(&self, p: Point2) -> Surface {
+        self.get(p)
+    }
+}
+
+impl Ground for Track {
+    fn surface(&self, p: Point2) -> Surface {
+        Track::surface(self, p)
+    }
+    fn colliders(&self) -> &[Collider] {
+        &self.colliders
+    }
+}
+
+impl Ground for World {
+    fn surface(&self, p: Point2) -> Surface {
+        self.get(p)
+    }
+    fn colliders(&self) -> &[Collider] {
+        match self.track() {
+            Some(track) => &track.colliders,
+            None => &[],
+        }
+    }
+}
+
+/// What a car is told to do in a tick: the controls held through it and the actions done at the start of it
+pub type TickInput = (Controls, Vec<Action>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Something the driver does once rather than holds down
+pub enum Action {
+    /// Put the car in a gear
+    Gear(i8),
+    /// Move the brake bias towards the front
+    AdjustBias(f32),
+    /// Steepen the rear wing by some degrees
+    AdjustWing(f32),
+}
+
+impl Action {
+    /// Does the action to a car
+    pub fn apply(&self, car: &mut Car) {
+        match *self {
+            // Gears the car doesn't have go to the nearest one it has
+            Action::Gear(gear) => {
+                let transmission = &car.setup.transmission;
+                car.gear = gear.max(-transmission.reverse_gears()).min(transmission.gears());
+            }
+            Action::AdjustBias(d) => car.setup.brakes.adjust_bias(d),
+            Action::AdjustWing(d) => car.setup.aero.adjust_wing(d),
+        }
+    }
+}
+
+/// The resources directory, in the workspace when running with cargo and next to the executable otherwise
+///
+/// This is for reading resources without a `Context`, like when nothing is drawn
+pub fn resources_dir() -> PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        return PathBuf::from(manifest_dir).join("resources")
+    }
+    env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("resources")))
+        .unwrap_or_else(|| PathBuf::from("resources"))
+}
+
+/// A deterministic simulation of some cars on some ground
+///
+/// Nothing in here looks at the clock, so the same cars, timestep, seed and inputs always give the same states
+pub struct Sim<G: Ground> {
+    /// The cars, which are always updated in this order
+    pub cars: Vec<Car>,
+    pub ground: G,
+    pub timestep: Timestep,
+    /// How many ticks have been simulated
+    pub tick: u64,
+    /// The only source of randomness in the simulation
+    pub rng: Rng,
+}
+
+impl<G: Ground> Sim<G> {
+    /// Makes a new simulation with randomness seeded with `seed`
+    pub fn new(cars: Vec<Car>, ground: G, timestep: Timestep, seed: u64) -> Self {
+        Sim {
+            cars,
+            ground,
+            timestep,
+            tick: 0,
+            rng: Rng::new(seed),
+        }
+    }
+    /// Does the actions of every car and then simulates one tick with their input
+    ///
+    /// `input` has the controls and actions of each car in the same order as the cars
+    pub fn tick(&mut self, input: &[TickInput]) {
+        for (car, &(_, ref actions)) in self.cars.iter_mut().zip(input) {
+            for action in actions {
+                action.apply(car);
+            }
+        }
+        let dt = self.timestep.dt();
+        for _ in 0..self.timestep.substeps {
+            for (car, &(ref controls, _)) in self.cars.iter_mut().zip(input) {
+                let surface = self.ground.surface(car.obj.pos);
+                car.update(controls, surface, dt, self.timestep.integrator);
+                for collider in self.ground.colliders() {
+                    if let Some(contact) = car.obb().collide(&collider.obb) {
+                        resolve(&contact, collider.material, car, None);
+                    }
+                }
+            }
+            // Then every pair of cars with each other
+            for i in 0..self.cars.len() {
+                let (done, rest) = self.cars.split_at_mut(i + 1);
+                let a = &mut done[i];
+                for b in rest {
+                    if let Some(contact) = a.obb().collide(&b.obb()) {
+                        resolve(&contact, Material::CAR, a, Some(b));
+                    }
+                }
+            }
+        }
+        self.tick += 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A small xorshift* random number generator that gives the same numbers on every platform
+pub struct Rng(u64);
+
+impl Rng {
+    /// Makes a generator from a seed, where any seed is fine
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed with a splitmix step so similar seeds don't give similar numbers and zero isn't stuck
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng((z ^ (z >> 31)) | 1)
+    }
+    /// The next random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    /// A random number in `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A 64-bit FNV-1a hash of the exact bits of some numbers
+pub struct Checksum(pub u64);
+
+impl Checksum {
+    pub fn new() -> Self {
+        Checksum(0xcbf2_9ce4_8422_2325)
+    }
+    /// Adds some bytes to the checksum
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+    /// Adds the bits of a float to the checksum
+    pub fn f32(&mut self, v: f32) {
+        let bits = v.to_bits();
+        self.bytes(&[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+    }
+}
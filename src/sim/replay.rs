@@ -0,0 +1,192 @@
+//! Recordings of everything the driver did, tick by tick
+//!
+//! A replay file starts with a header of `key value` lines describing the simulation,
+//! followed by a line of `---` and then one line per run of identical ticks:
+//!
+//! ```text
+//! THROTTLE BRAKE CLUTCH STEERING [HANDBRAKE] [*COUNT] [ACTION...]
+//! ```
+//!
+//! The pedals and the handbrake go from 0 to 1 and the steering from -1 for full lock to the left to 1 for full lock to the right,
+//! the handbrake is left out while it's off,
+//! `*COUNT` repeats the line for that many ticks and the actions are done before the first of them.
+//! Actions are `g<gear>` for changing gear, `b<change>` for moving the brake bias and `w<change>` for moving the wing.
+
+use std::fs::File;
+use std::io::{self, Write, BufRead, BufReader, BufWriter};
+use std::path::Path;
+
+use super::*;
+use obj::{Integrator, Object, Controls};
+use world::WorldName;
+
+/// Writes a replay file while driving
+pub struct Recorder {
+    out: BufWriter<File>,
+    /// The last tick's input and how many ticks in a row it has been the same
+    pending: Option<(TickInput, u32)>,
+}
+
+impl Recorder {
+    /// Creates the replay file and writes the header for a simulation in `world` starting at `start`
+    pub fn create<P: AsRef<Path>, G: Ground>(path: P, sim: &Sim<G>, world: &WorldName, seed: u64, start: Object) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# car-driver replay")?;
+        writeln!(out, "seed {}", seed)?;
+        writeln!(out, "tick-rate {}", sim.timestep.tick_rate)?;
+        writeln!(out, "substeps {}", sim.timestep.substeps)?;
+        writeln!(out, "integrator {}", sim.timestep.integrator)?;
+        writeln!(out, "{}", world)?;
+        writeln!(out, "start {} {} {}", start.pos.x, start.pos.y, start.rot)?;
+        writeln!(out, "---")?;
+
+        Ok(Recorder {
+            out,
+            pending: None,
+        })
+    }
+    /// Records the controls and actions of a tick
+    pub fn record(&mut self, controls: &Controls, actions: &[Action]) -> io::Result<()> {
+        if let Some((ref last, ref mut count)) = self.pending {
+            if actions.is_empty() && last.0 == *controls {
+                *count += 1;
+                return Ok(())
+            }
+        }
+        self.flush_pending()?;
+        self.pending = Some(((*controls, actions.to_vec()), 1));
+        Ok(())
+    }
+    fn flush_pending(&mut self) -> io::Result<()> {
+        if let Some(((c, actions), count)) = self.pending.take() {
+            write!(self.out, "{} {} {} {}", c.throttle, c.brake, c.clutch, c.steering)?;
+            if c.handbrake != 0. {
+                write!(self.out, " {}", c.handbrake)?;
+            }
+            if count > 1 {
+                write!(self.out, " *{}", count)?;
+            }
+            for action in actions {
+                match action {
+                    Action::Gear(g) => write!(self.out, " g{}", g)?,
+                    Action::AdjustBias(d) => write!(self.out, " b{}", d)?,
+                    Action::AdjustWing(d) => write!(self.out, " w{}", d)?,
+                }
+            }
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_pending().and_then(|_| self.out.flush()) {
+            eprintln!("Couldn't finish writing the replay: {}", e);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A loaded replay file
+pub struct Replay {
+    pub seed: u64,
+    pub timestep: Timestep,
+    /// The world the replay was recorded in
+    pub world: WorldName,
+    /// Where the car started
+    pub start: Object,
+    /// The controls and actions of each tick
+    ticks: Vec<TickInput>,
+}
+
+impl Replay {
+    /// Loads a replay file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut replay = Replay {
+            seed: 0,
+            timestep: Timestep::default(),
+            // Replays from before there were tracks were all on the surface map
+            world: WorldName::SurfaceMap,
+            start: Object::new(Point2::new(0., 0.)),
+            ticks: Vec::new(),
+        };
+        let mut in_header = true;
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line in replay: {}", line);
+            let mut words = line.split_whitespace().peekable();
+            let first = words.next().unwrap();
+
+            if in_header {
+                let values: Vec<_> = words.collect();
+                match (first, &*values) {
+                    ("---", []) => in_header = false,
+                    ("seed", [v]) => replay.seed = v.parse().map_err(|_| bad())?,
+                    ("tick-rate", [v]) => replay.timestep.tick_rate = v.parse().map_err(|_| bad())?,
+                    ("substeps", [v]) => replay.timestep.substeps = v.parse().map_err(|_| bad())?,
+                    ("integrator", [v]) => replay.timestep.integrator = v.parse::<Integrator>()?,
+                    ("track", [name]) => replay.world = WorldName::Track(name.to_string()),
+                    ("surface-map", []) => replay.world = WorldName::SurfaceMap,
+                    ("start", [x, y, rot]) => {
+                        let parse = |v: &str| v.parse::<f32>().map_err(|_| bad());
+                        replay.start = Object {
+                            pos: Point2::new(parse(x)?, parse(y)?),
+                            rot: parse(rot)?,
+                        };
+                    }
+                    _ => return Err(bad()),
+                }
+                continue
+            }
+
+            let value = |v: Option<&str>| v.and_then(|v| v.parse::<f32>().ok()).ok_or_else(bad);
+            let controls = Controls {
+                throttle: value(Some(first))?,
+                brake: value(words.next())?,
+                clutch: value(words.next())?,
+                steering: value(words.next())?,
+                handbrake: match words.peek().and_then(|v| v.parse::<f32>().ok()) {
+                    Some(handbrake) => {
+                        words.next();
+                        handbrake
+                    }
+                    None => 0.,
+                },
+            };
+            let mut count = 1;
+            let mut actions = Vec::new();
+            for word in words {
+                let kind = word.chars().next().unwrap();
+                let value = &word[kind.len_utf8()..];
+                match kind {
+                    '*' => count = value.parse().map_err(|_| bad())?,
+                    'g' => actions.push(Action::Gear(value.parse().map_err(|_| bad())?)),
+                    'b' => actions.push(Action::AdjustBias(value.parse().map_err(|_| bad())?)),
+                    'w' => actions.push(Action::AdjustWing(value.parse().map_err(|_| bad())?)),
+                    _ => return Err(bad()),
+                }
+            }
+            replay.ticks.push((controls, actions));
+            for _ in 1..count {
+                replay.ticks.push((controls, Vec::new()));
+            }
+        }
+
+        Ok(replay)
+    }
+    /// Number of ticks in the replay
+    pub fn len(&self) -> u64 {
+        self.ticks.len() as u64
+    }
+    /// The controls and actions of a tick, or `None` if the replay is over
+    pub fn get(&self, tick: u64) -> Option<(&Controls, &[Action])> {
+        self.ticks.get(tick as usize).map(|&(ref controls, ref actions)| (controls, &**actions))
+    }
+}
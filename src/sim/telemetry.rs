@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+
+use super::*;
+use ::PIXELS_PER_METER;
+
+/// The columns of a telemetry log, positions are in metres and everything else in SI units
+const HEADER: &str = "tick,time,x,y,heading,vx,vy,speed,engine_speed,gear,throttle,brake,clutch,steering_angle,\
+traction_x,traction_y,drag_x,drag_y,grip_x,grip_y,brake_x,brake_y,acc_x,acc_y,surface,\
+brake_temp_front,brake_temp_rear,roll,pitch,\
+tyre_temp_fl,tyre_temp_fr,tyre_temp_rl,tyre_temp_rr,tyre_wear_fl,tyre_wear_fr,tyre_wear_rl,tyre_wear_rr";
+
+/// Logs the state of a car every tick to a CSV file
+pub struct TelemetryLog {
+    out: BufWriter<File>,
+}
+
+impl TelemetryLog {
+    /// Creates the log file and writes the header
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", HEADER)?;
+        Ok(TelemetryLog {
+            out,
+        })
+    }
+    /// Writes a row with the current state of the `car`th car in the simulation
+    pub fn log<G: Ground>(&mut self, sim: &Sim<G>, car: usize) -> io::Result<()> {
+        let car = &sim.cars[car];
+        let d = match car.diagnostics {
+            Some(d) => d,
+            None => return Ok(()),
+        };
+        let f = &d.forces;
+
+        write!(self.out, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},",
+            sim.tick, sim.tick as f32 * sim.timestep.tick(),
+            car.obj.pos.x / PIXELS_PER_METER, car.obj.pos.y / PIXELS_PER_METER, car.obj.rot,
+            car.velocity.x, car.velocity.y, car.velocity.norm(),
+            car.engine_speed, car.gear, car.throttle, car.brake, car.clutch, car.steering_angle)?;
+        write!(self.out, "{},{},{},{},{},{},{},{},{},{},{:?},",
+            f.traction.x, f.traction.y, f.drag.x, f.drag.y, f.grip.x, f.grip.y, f.brake.x, f.brake.y,
+            d.acc.x, d.acc.y, d.surface)?;
+        write!(self.out, "{},{},{},{}",
+            car.brake_temp[0], car.brake_temp[1], car.suspension.roll, car.suspension.pitch)?;
+        for tyre in &car.tyres {
+            write!(self.out, ",{}", tyre.surface_temp)?;
+        }
+        for tyre in &car.tyres {
+            write!(self.out, ",{}", tyre.wear)?;
+        }
+        writeln!(self.out)
+    }
+}
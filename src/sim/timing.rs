@@ -0,0 +1,278 @@
+//! Lap timing by gates the car drives through
+//!
+//! A gates file has a line per gate with its two ends in metres, going from the driver's left to their right:
+//!
+//! ```text
+//! start <x1> <y1> <x2> <y2>
+//! checkpoint <x1> <y1> <x2> <y2>
+//! finish <x1> <y1> <x2> <y2>
+//! ```
+//!
+//! The checkpoints have to be passed in the order they are listed for a lap to count
+//! and split the lap into sectors. Without a finish line a lap ends when the car crosses the start line again,
+//! with one it is a run from the start line to the finish line. All times are counted in ticks.
+
+use ggez::{Context, GameResult, GameError};
+use ggez::graphics::{Point2, Vector2};
+
+use std::io::Read;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use ::PIXELS_PER_METER;
+use world::Track;
+
+#[derive(Debug, Clone, Copy)]
+/// A line across the track in pixels from the driver's left to their right
+pub struct Gate {
+    pub a: Point2,
+    pub b: Point2,
+}
+
+impl Gate {
+    /// Whether moving from `from` to `to` crosses the gate forwards
+    pub fn crossed(&self, from: Point2, to: Point2) -> bool {
+        let cross = |u: Vector2, v: Vector2| u.x * v.y - u.y * v.x;
+        let gate = self.b - self.a;
+        let path = to - from;
+        // `from` has to be behind the gate and `to` in front of it, and the ends of the gate on either side of the path
+        cross(gate, from - self.a) > 0. && cross(gate, to - self.a) <= 0.
+            && cross(path, self.a - from) * cross(path, self.b - from) <= 0.
+    }
+}
+
+impl FromStr for Gate {
+    type Err = String;
+    /// Reads a gate written as `x1,y1,x2,y2` in metres
+    fn from_str(s: &str) -> Result<Self, String> {
+        let bad = || format!("Bad gate {}, expected x1,y1,x2,y2 in metres", s);
+        let values = s.split(',').map(|v| v.trim().parse::<f32>().map_err(|_| bad())).collect::<Result<Vec<_>, _>>()?;
+        match *values {
+            [x1, y1, x2, y2] => Ok(Gate {
+                a: Point2::new(x1, y1) * PIXELS_PER_METER,
+                b: Point2::new(x2, y2) * PIXELS_PER_METER,
+            }),
+            _ => Err(bad()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The start line, the checkpoints and the finish line of a track
+pub struct Gates {
+    pub start: Gate,
+    pub checkpoints: Vec<Gate>,
+    /// Where runs end if it isn't the start line
+    pub finish: Option<Gate>,
+}
+
+impl Gates {
+    /// Loads the gates of a track from `/tracks/<name>.gates` in the resources, if it has any
+    pub fn load(ctx: &mut Context, track: &str) -> GameResult<Option<Self>> {
+        let path = format!("/tracks/{}.gates", track);
+        if !ctx.filesystem.is_file(&path) {
+            return Ok(None)
+        }
+        let mut src = String::new();
+        ctx.filesystem.open(&path)?.read_to_string(&mut src)?;
+        Gates::parse(&src)
+            .map(Some)
+            .map_err(|e| GameError::ResourceLoadError(format!("Couldn't load gates {}: {}", path, e)))
+    }
+    /// Reads the gates from the contents of a gates file
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut start = None;
+        let mut checkpoints = Vec::new();
+        let mut finish = None;
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line in gates: {}", line);
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap();
+            let values = words.map(|v| v.parse::<f32>().map_err(|_| bad())).collect::<Result<Vec<_>, _>>()?;
+            let gate = match *values {
+                [x1, y1, x2, y2] => Gate {
+                    a: Point2::new(x1, y1) * PIXELS_PER_METER,
+                    b: Point2::new(x2, y2) * PIXELS_PER_METER,
+                },
+                _ => return Err(bad()),
+            };
+            match key {
+                "start" => start = Some(gate),
+                "checkpoint" => checkpoints.push(gate),
+                "finish" => finish = Some(gate),
+                _ => return Err(bad()),
+            }
+        }
+        Ok(Gates {
+            start: start.ok_or_else(|| "The gates need a start line".to_owned())?,
+            checkpoints,
+            finish,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A finished lap
+pub struct Lap {
+    /// Length of the lap in ticks
+    pub ticks: u64,
+    /// Length of each sector in ticks
+    pub sectors: Vec<u64>,
+    /// Whether every checkpoint was passed in order
+    pub valid: bool,
+    /// Distance along the track since the start line at every tick of the lap, if the lap was on a track
+    trace: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+/// Times laps and sectors of a car
+pub struct LapTimer {
+    gates: Gates,
+    /// The tick the current lap started at, `None` until the start line is crossed
+    lap_start: Option<u64>,
+    /// Ticks into the lap each checkpoint has been passed at
+    splits: Vec<u64>,
+    /// The checkpoint that should be passed next
+    next_checkpoint: usize,
+    valid: bool,
+    trace: Vec<f32>,
+    /// Every finished lap
+    pub laps: Vec<Lap>,
+    /// Index of the fastest valid lap
+    best: Option<usize>,
+}
+
+impl LapTimer {
+    pub fn new(gates: Gates) -> Self {
+        LapTimer {
+            gates,
+            lap_start: None,
+            splits: Vec::new(),
+            next_checkpoint: 0,
+            valid: true,
+            trace: Vec::new(),
+            laps: Vec::new(),
+            best: None,
+        }
+    }
+    /// Checks for gates crossed by a car moving from `from` to `to` during a tick, ending at `tick`
+    ///
+    /// The distance along the `track`, if there is one, is kept for the live delta.
+    /// Returns the lap that was just finished, if any.
+    pub fn update(&mut self, from: Point2, to: Point2, tick: u64, track: Option<&Track>) -> Option<&Lap> {
+        let crossed_start = self.gates.start.crossed(from, to);
+        let crossed_finish = match self.gates.finish {
+            Some(ref finish) => finish.crossed(from, to),
+            None => crossed_start,
+        };
+        let mut finished = false;
+        if let Some(lap_start) = self.lap_start {
+            let lap_ticks = tick - lap_start;
+            for (i, gate) in self.gates.checkpoints.iter().enumerate() {
+                if i >= self.next_checkpoint && gate.crossed(from, to) {
+                    // Skipping a checkpoint ruins the lap
+                    if i > self.next_checkpoint {
+                        self.valid = false;
+                    }
+                    while self.splits.len() <= i {
+                        self.splits.push(lap_ticks);
+                    }
+                    self.next_checkpoint = i + 1;
+                }
+            }
+            if let Some(track) = track {
+                // Near the start line the distance wraps around, so take whichever is closest to the last one
+                // A distance that isn't a number is kept as it is
+                let (d, length) = (track.distance(to), track.length());
+                let last = self.trace.last().cloned().unwrap_or(0.);
+                let d = [d - length, d, d + length].iter().cloned()
+                    .min_by(|a, b| (a - last).abs().partial_cmp(&(b - last).abs()).unwrap_or(Ordering::Equal))
+                    .unwrap();
+                self.trace.push(d);
+            }
+            if crossed_finish {
+                self.finish_lap(lap_ticks);
+                self.lap_start = None;
+                finished = true;
+            }
+        }
+        if crossed_start {
+            self.lap_start = Some(tick);
+            self.splits.clear();
+            self.next_checkpoint = 0;
+            self.valid = true;
+            self.trace.clear();
+        }
+        if finished { self.laps.last() } else { None }
+    }
+    fn finish_lap(&mut self, ticks: u64) {
+        let valid = self.valid && self.next_checkpoint == self.gates.checkpoints.len();
+        let mut sectors = Vec::with_capacity(self.splits.len() + 1);
+        let mut last = 0;
+        for &split in self.splits.iter().chain(Some(ticks).iter()) {
+            sectors.push(split - last);
+            last = split;
+        }
+        self.laps.push(Lap {
+            ticks,
+            sectors,
+            valid,
+            trace: self.trace.clone(),
+        });
+        let i = self.laps.len() - 1;
+        if valid && self.best().map(|b| ticks < b.ticks).unwrap_or(true) {
+            self.best = Some(i);
+        }
+    }
+    /// Ticks into the current lap at `tick`, if a lap is going on
+    pub fn current(&self, tick: u64) -> Option<u64> {
+        self.lap_start.map(|start| tick - start)
+    }
+    /// The number of the current lap, counting from 1
+    pub fn lap_number(&self) -> usize {
+        self.laps.len() + 1
+    }
+    /// Whether the current lap can still count
+    pub fn current_valid(&self) -> bool {
+        self.valid
+    }
+    /// Ticks into the current lap each checkpoint was passed at
+    pub fn splits(&self) -> &[u64] {
+        &self.splits
+    }
+    pub fn last(&self) -> Option<&Lap> {
+        self.laps.last()
+    }
+    pub fn best(&self) -> Option<&Lap> {
+        self.best.map(|i| &self.laps[i])
+    }
+    /// How many ticks behind the best lap the current lap is at `tick`
+    ///
+    /// On a track this compares the time taken to get as far along the track,
+    /// otherwise it compares the last checkpoint passed
+    pub fn delta(&self, tick: u64) -> Option<i64> {
+        let best = self.best()?;
+        let current = self.current(tick)?;
+        if let (Some(&d), false) = (self.trace.last(), best.trace.is_empty()) {
+            // The trace starts at the first tick of the lap
+            let best_ticks = best.trace.iter().position(|&bd| bd >= d).map(|p| p + 1).unwrap_or(best.trace.len());
+            return Some(current as i64 - best_ticks as i64)
+        }
+        let n = self.splits.len();
+        if n == 0 {
+            return None
+        }
+        let best_split: u64 = best.sectors[..n].iter().sum();
+        Some(self.splits[n - 1] as i64 - best_split as i64)
+    }
+}
+
+/// Formats a number of ticks as minutes, seconds and milliseconds
+pub fn format_ticks(ticks: u64, tick_rate: u32) -> String {
+    let millis = ticks * 1000 / tick_rate as u64;
+    format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
@@ -0,0 +1,177 @@
+//! Telemetry sent over UDP for external dashboards and data loggers
+//!
+//! Like most racing sim telemetry protocols every tick is sent as one fixed size datagram.
+//! All fields are little-endian and the packet is 68 bytes long:
+//!
+//! | Offset | Type    | Field                                              |
+//! |--------|---------|----------------------------------------------------|
+//! | 0      | [u8; 4] | Magic `CDRV`                                       |
+//! | 4      | u32     | Format version, currently 1                        |
+//! | 8      | u32     | Tick number                                        |
+//! | 12     | f32     | Simulated time in s                                |
+//! | 16     | f32     | Speed in m/s                                       |
+//! | 20     | f32     | Engine speed in RPM                                |
+//! | 24     | f32     | Redline in RPM                                     |
+//! | 28     | i8      | Gear, -1 is reverse and 0 is neutral               |
+//! | 29     | [u8; 3] | Padding                                            |
+//! | 32     | f32     | Throttle from 0 to 1                               |
+//! | 36     | f32     | Brake from 0 to 1                                  |
+//! | 40     | f32     | Clutch from 0 (engaged) to 1                       |
+//! | 44     | f32     | Steering angle in radians, positive to the right   |
+//! | 48     | f32     | Lateral acceleration in g, positive to the left    |
+//! | 52     | f32     | Longitudinal acceleration in g, positive forwards  |
+//! | 56     | f32     | X position in m                                    |
+//! | 60     | f32     | Y position in m, positive downwards on the screen  |
+//! | 64     | f32     | Heading in radians, 0 along the x-axis             |
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use super::*;
+use ::PIXELS_PER_METER;
+
+/// Identifies the packets
+pub const MAGIC: [u8; 4] = *b"CDRV";
+/// Version of the packet format
+pub const VERSION: u32 = 1;
+/// Size of a packet in bytes
+pub const PACKET_SIZE: usize = 68;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The contents of a telemetry packet
+pub struct Packet {
+    pub tick: u32,
+    pub time: f32,
+    pub speed: f32,
+    pub rpm: f32,
+    pub redline_rpm: f32,
+    pub gear: i8,
+    pub throttle: f32,
+    pub brake: f32,
+    pub clutch: f32,
+    pub steering_angle: f32,
+    pub lateral_g: f32,
+    pub longitudinal_g: f32,
+    pub x: f32,
+    pub y: f32,
+    pub heading: f32,
+}
+
+impl Packet {
+    /// Makes a packet of the current state of the `car`th car in the simulation
+    pub fn new<G: Ground>(sim: &Sim<G>, car: usize) -> Self {
+        let car = &sim.cars[car];
+        let (lateral_g, longitudinal_g) = car.g_forces();
+
+        Packet {
+            tick: sim.tick as u32,
+            time: sim.tick as f32 * sim.timestep.tick(),
+            speed: car.velocity.norm(),
+            rpm: car.engine_speed,
+            redline_rpm: car.setup.engine.redline_rpm,
+            gear: car.gear,
+            throttle: car.throttle,
+            brake: car.brake,
+            clutch: car.clutch,
+            steering_angle: car.steering_angle,
+            lateral_g,
+            longitudinal_g,
+            x: car.obj.pos.x / PIXELS_PER_METER,
+            y: car.obj.pos.y / PIXELS_PER_METER,
+            heading: car.obj.rot,
+        }
+    }
+    /// Packs the packet into bytes
+    pub fn encode(&self) -> [u8; PACKET_SIZE] {
+        let mut buf = [0; PACKET_SIZE];
+        buf[0..4].copy_from_slice(&MAGIC);
+        put_u32(&mut buf[4..], VERSION);
+        put_u32(&mut buf[8..], self.tick);
+        buf[28] = self.gear as u8;
+
+        let floats = [
+            (12, self.time), (16, self.speed), (20, self.rpm), (24, self.redline_rpm),
+            (32, self.throttle), (36, self.brake), (40, self.clutch), (44, self.steering_angle),
+            (48, self.lateral_g), (52, self.longitudinal_g), (56, self.x), (60, self.y), (64, self.heading),
+        ];
+        for &(offset, v) in &floats {
+            put_u32(&mut buf[offset..], v.to_bits());
+        }
+        buf
+    }
+    /// Unpacks a packet, or `None` if it isn't a packet of this version
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() != PACKET_SIZE || buf[0..4] != MAGIC || get_u32(&buf[4..]) != VERSION {
+            return None
+        }
+        let f = |offset: usize| f32::from_bits(get_u32(&buf[offset..]));
+        Some(Packet {
+            tick: get_u32(&buf[8..]),
+            time: f(12),
+            speed: f(16),
+            rpm: f(20),
+            redline_rpm: f(24),
+            gear: buf[28] as i8,
+            throttle: f(32),
+            brake: f(36),
+            clutch: f(40),
+            steering_angle: f(44),
+            lateral_g: f(48),
+            longitudinal_g: f(52),
+            x: f(56),
+            y: f(60),
+            heading: f(64),
+        })
+    }
+}
+
+fn put_u32(buf: &mut [u8], v: u32) {
+    buf[0] = v as u8;
+    buf[1] = (v >> 8) as u8;
+    buf[2] = (v >> 16) as u8;
+    buf[3] = (v >> 24) as u8;
+}
+
+fn get_u32(buf: &[u8]) -> u32 {
+    buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24
+}
+
+/// Sends a telemetry packet every tick to an address
+pub struct UdpTelemetry {
+    socket: UdpSocket,
+    addr: SocketAddr,
+}
+
+impl UdpTelemetry {
+    /// Makes a socket for sending to `addr`, which may be a broadcast address
+    pub fn new(addr: SocketAddr) -> io::Result<Self> {
+        let bind: SocketAddr = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse().unwrap();
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_broadcast(true)?;
+        Ok(UdpTelemetry {
+            socket,
+            addr,
+        })
+    }
+    /// Sends the current state of the `car`th car in the simulation
+    pub fn send<G: Ground>(&self, sim: &Sim<G>, car: usize) {
+        // Nobody might be listening, which is fine
+        let _ = self.socket.send_to(&Packet::new(sim, car).encode(), self.addr);
+    }
+}
+
+/// Prints every telemetry packet received on `addr` until something goes wrong
+pub fn listen(addr: SocketAddr) -> io::Result<()> {
+    let socket = UdpSocket::bind(addr)?;
+    let mut buf = [0; 1024];
+    println!("Listening for telemetry on {}", addr);
+    loop {
+        let (len, from) = socket.recv_from(&mut buf)?;
+        match Packet::decode(&buf[..len]) {
+            Some(p) => println!("{} tick {:6} {:6.1} km/h {:5.0} RPM gear {:2} T {:4.2} B {:4.2} C {:4.2} lat {:5.2} g long {:5.2} g at ({:.1}, {:.1})",
+                from, p.tick, p.speed * 3.6, p.rpm, p.gear, p.throttle, p.brake, p.clutch,
+                p.lateral_g, p.longitudinal_g, p.x, p.y),
+            None => println!("{} sent {} bytes that aren't a telemetry packet", from, len),
+        }
+    }
+}
@@ -82,6 +82,14 @@ impl Assets {
             text
         })
     }
+    /// Make a positional text object centred on a point
+    pub fn text_centred(&self, context: &mut Context, pos: Point2, text: &str) -> GameResult<PosText> {
+        let text = Text::new(context, text, &self.font)?;
+        Ok(PosText{
+            pos: Point2::new(pos.x - text.width() as f32 / 2., pos.y - text.height() as f32 / 2.),
+            text
+        })
+    }
     /// Make a postional text object from the right side of the screen
     pub fn text_ra(&self, context: &mut Context, x: f32, y: f32, text: &str) -> GameResult<PosText> {
         let text = Text::new(context, text, &self.font)?;
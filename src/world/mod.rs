@@ -0,0 +1,209 @@
+use ggez::{Context, GameResult, GameError};
+use ggez::graphics::{self, Point2, Image};
+use image;
+
+use std::io::Read;
+use std::fmt;
+use std::str::FromStr;
+
+mod track;
+
+pub use self::track::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The kinds of ground a car can drive on
+pub enum Surface {
+    Asphalt,
+    Gravel,
+    Grass,
+    Ice,
+}
+
+impl Surface {
+    /// Every surface
+    pub const ALL: [Surface; 4] = [Surface::Asphalt, Surface::Gravel, Surface::Grass, Surface::Ice];
+
+    /// The colour of the surface in a surface map
+    pub fn colour(self) -> [u8; 3] {
+        match self {
+            Surface::Asphalt => [80, 80, 80],
+            Surface::Gravel => [200, 180, 120],
+            Surface::Grass => [60, 140, 50],
+            Surface::Ice => [200, 230, 255],
+        }
+    }
+    /// Friction coefficient of tyres on this surface
+    ///
+    /// `None` means the tyres' own friction is used
+    pub fn friction(self) -> Option<f32> {
+        match self {
+            Surface::Asphalt => None,
+            Surface::Gravel => Some(0.6),
+            Surface::Grass => Some(0.45),
+            Surface::Ice => Some(0.1),
+        }
+    }
+    /// Rolling resistance on this surface
+    ///
+    /// `None` means the car's own rolling resistance is used
+    pub fn rolling_r(self) -> Option<f32> {
+        match self {
+            Surface::Asphalt => None,
+            Surface::Gravel => Some(400.),
+            Surface::Grass => Some(250.),
+            Surface::Ice => Some(8.),
+        }
+    }
+    /// The surface with the colour closest to the given one
+    fn closest(rgb: &[u8]) -> Surface {
+        let dist = |s: &Surface| s.colour().iter().zip(rgb).map(|(&a, &b)| (a as i32 - b as i32).pow(2)).sum::<i32>();
+        *Surface::ALL.iter().min_by_key(|s| dist(s)).unwrap()
+    }
+}
+
+impl FromStr for Surface {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "asphalt" => Ok(Surface::Asphalt),
+            "gravel" => Ok(Surface::Gravel),
+            "grass" => Ok(Surface::Grass),
+            "ice" => Ok(Surface::Ice),
+            _ => Err(format!("Unknown surface {}, expected asphalt, gravel, grass or ice", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Which world to drive in
+pub enum WorldName {
+    /// A track from `/tracks/<name>.trk`
+    Track(String),
+    /// The surface map in `/surface.png`
+    SurfaceMap,
+}
+
+impl Default for WorldName {
+    fn default() -> Self {
+        WorldName::Track("oval".to_owned())
+    }
+}
+
+impl WorldName {
+    /// A short name for the world without spaces, for use in file names
+    pub fn file_name(&self) -> &str {
+        match *self {
+            WorldName::Track(ref name) => name,
+            WorldName::SurfaceMap => "surface-map",
+        }
+    }
+}
+
+impl fmt::Display for WorldName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WorldName::Track(ref name) => write!(f, "track {}", name),
+            WorldName::SurfaceMap => "surface-map".fmt(f),
+        }
+    }
+}
+
+/// The world the cars drive in
+pub enum World {
+    Track(Track),
+    SurfaceMap(SurfaceMap),
+}
+
+impl World {
+    /// Loads the world with the given name from the resources
+    pub fn load(ctx: &mut Context, name: &WorldName) -> GameResult<Self> {
+        Ok(match *name {
+            WorldName::Track(ref name) => World::Track(Track::load(ctx, name)?),
+            WorldName::SurfaceMap => World::SurfaceMap(SurfaceMap::load(ctx, "/surface.png", 10.)?),
+        })
+    }
+    /// The track, if the world is one
+    pub fn track(&self) -> Option<&Track> {
+        match *self {
+            World::Track(ref track) => Some(track),
+            World::SurfaceMap(_) => None,
+        }
+    }
+    /// Gets the surface at a point in the world
+    pub fn get(&self, p: Point2) -> Surface {
+        match *self {
+            World::Track(ref track) => track.surface(p),
+            World::SurfaceMap(ref map) => map.get(p),
+        }
+    }
+    /// Draws the world
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        match *self {
+            World::Track(ref track) => track.draw(ctx),
+            World::SurfaceMap(ref map) => map.draw(ctx),
+        }
+    }
+}
+
+/// A grid of surfaces covering the world
+pub struct SurfaceMap {
+    surfaces: Vec<Surface>,
+    width: u32,
+    height: u32,
+    /// How many pixels in the world one cell in the map covers
+    scale: f32,
+    /// The surface used outside the map
+    outside: Surface,
+    img: Image,
+}
+
+impl SurfaceMap {
+    /// Loads a colour-coded surface map image
+    pub fn load(ctx: &mut Context, path: &str, scale: f32) -> GameResult<Self> {
+        let mut bytes = Vec::new();
+        ctx.filesystem.open(path)?.read_to_end(&mut bytes)?;
+        let img = image::load_from_memory(&bytes)
+            .map_err(|e| GameError::ResourceLoadError(format!("Couldn't load surface map {}: {}", path, e)))?
+            .to_rgba();
+        let (width, height) = img.dimensions();
+        // The image it's drawn with can't be any bigger
+        if width > u16::max_value() as u32 || height > u16::max_value() as u32 {
+            return Err(GameError::ResourceLoadError(format!("The surface map {} is bigger than {} pixels", path, u16::max_value())));
+        }
+
+        let surfaces: Vec<_> = img.into_raw().chunks(4).map(Surface::closest).collect();
+        // Redraw the map in the exact surface colours
+        let rgba: Vec<_> = surfaces.iter().flat_map(|s| {
+            let [r, g, b] = s.colour();
+            vec![r, g, b, 255]
+        }).collect();
+        let img = Image::from_rgba8(ctx, width as u16, height as u16, &rgba)?;
+
+        Ok(SurfaceMap {
+            surfaces,
+            width,
+            height,
+            scale,
+            outside: Surface::Grass,
+            img,
+        })
+    }
+    /// Gets the surface at a point in the world
+    pub fn get(&self, p: Point2) -> Surface {
+        let (x, y) = (p.x / self.scale, p.y / self.scale);
+        if x < 0. || y < 0. || x >= self.width as f32 || y >= self.height as f32 {
+            self.outside
+        } else {
+            self.surfaces[y as usize * self.width as usize + x as usize]
+        }
+    }
+    /// Draws the map
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let drawparams = graphics::DrawParam {
+            dest: Point2::new(0., 0.),
+            scale: Point2::new(self.scale, self.scale),
+            .. Default::default()
+        };
+        graphics::draw_ex(ctx, &self.img, drawparams)
+    }
+}
@@ -0,0 +1,414 @@
+//! Race tracks made from a closed centreline
+//!
+//! A track file has one `key value...` line per setting, all lengths are in metres:
+//!
+//! ```text
+//! name <name>
+//! width <metres>                    width of the road where a point doesn't give one
+//! kerb <width> <radius>             kerbs along both edges of corners tighter than the radius
+//! runoff <surface> <width>          the surface and width of the run-off area outside the kerbs
+//! walls                             walls along the outside of the run-off areas
+//! grid <slots> <spacing>            the start grid behind the start line
+//! point <x> <y> [width]             a point on the centreline
+//! cone <x> <y>                      a traffic cone
+//! barrier <x> <y> <length> <angle>  a tyre barrier turned `angle` degrees clockwise from the x-axis
+//! ```
+//!
+//! The centreline is a closed Catmull-Rom spline through the points in driving order,
+//! with the start/finish line at the first point.
+
+use ggez::{Context, GameResult, GameError};
+use ggez::graphics::{self, Point2, Vector2, Color, Mesh, Drawable};
+
+use std::io::Read;
+use std::cmp::Ordering;
+
+use super::Surface;
+use ::{Object, Obb, Collider, Material, PIXELS_PER_METER, angle_from_vec};
+
+/// How many samples of the centreline there are between two points
+const SAMPLES_PER_SPAN: usize = 20;
+/// Kerb stripes alternate between these colours
+const KERB_RED: Color = Color{r:0.8,g:0.1,b:0.1,a:1.};
+const KERB_WHITE: Color = Color{r:0.95,g:0.95,b:0.95,a:1.};
+const WALL: Color = Color{r:0.25,g:0.25,b:0.3,a:1.};
+/// Thickness of the walls in m
+const WALL_WIDTH: f32 = 0.6;
+const CONE: Color = Color{r:1.,g:0.45,b:0.05,a:1.};
+const BARRIER: Color = Color{r:0.12,g:0.12,b:0.12,a:1.};
+/// Width of a cone's base in m
+const CONE_SIZE: f32 = 0.5;
+/// Thickness of a tyre barrier in m
+const BARRIER_WIDTH: f32 = 1.;
+
+#[derive(Debug, Clone, Copy)]
+/// A point along the centreline
+struct Sample {
+    /// Position in the world in pixels
+    pos: Point2,
+    /// Unit vector pointing to the right of the driving direction
+    normal: Vector2,
+    /// Width of the road in m
+    width: f32,
+    /// Distance from the start line in m
+    dist: f32,
+    /// Whether the road has kerbs here
+    kerb: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Where a point is relative to the track
+pub struct TrackPos {
+    /// Distance along the centreline from the start line in m
+    pub distance: f32,
+    /// Distance from the centreline in m, positive to the right of the driving direction
+    pub offset: f32,
+    /// Width of the road there in m
+    pub width: f32,
+}
+
+/// A closed race track
+pub struct Track {
+    pub name: String,
+    samples: Vec<Sample>,
+    /// Length of the centreline in m
+    length: f32,
+    kerb_width: f32,
+    runoff: Surface,
+    runoff_width: f32,
+    /// The walls on the left and right of the track as closed lines in pixels
+    pub walls: Vec<Vec<Point2>>,
+    /// The cones and barriers and their colours
+    obstacles: Vec<(Color, Obb)>,
+    /// The walls and obstacles
+    pub colliders: Vec<Collider>,
+    grid_slots: u32,
+    grid_spacing: f32,
+    /// The pieces of the track to draw, bottom first
+    meshes: Vec<(Color, Mesh)>,
+}
+
+impl Track {
+    /// Loads `/tracks/<name>.trk` from the resources and makes the meshes for drawing it
+    pub fn load(ctx: &mut Context, name: &str) -> GameResult<Self> {
+        let path = format!("/tracks/{}.trk", name);
+        let mut src = String::new();
+        ctx.filesystem.open(&path)?.read_to_string(&mut src)?;
+        let mut track = Track::parse(&src)
+            .map_err(|e| GameError::ResourceLoadError(format!("Couldn't load track {}: {}", path, e)))?;
+        track.build_meshes(ctx)?;
+        Ok(track)
+    }
+    /// Reads a track from the contents of a track file without anything to draw
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut name = String::new();
+        let mut width = 12.;
+        let (mut kerb_width, mut kerb_radius) = (0., 0.);
+        let (mut runoff, mut runoff_width) = (Surface::Grass, 0.);
+        let mut walls = false;
+        let (mut grid_slots, mut grid_spacing) = (1, 8.);
+        let mut points: Vec<(Point2, Option<f32>)> = Vec::new();
+        let mut obstacles = Vec::new();
+        let mut colliders = Vec::new();
+
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let bad = || format!("Bad line: {}", line);
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap();
+            let values: Vec<_> = words.collect();
+            let num = |v: &str| v.parse::<f32>().map_err(|_| bad());
+
+            match (key, &*values) {
+                ("name", _) if !values.is_empty() => name = values.join(" "),
+                ("width", [w]) => width = num(w)?,
+                ("kerb", [w, r]) => {
+                    kerb_width = num(w)?;
+                    kerb_radius = num(r)?;
+                }
+                ("runoff", [s, w]) => {
+                    runoff = s.parse()?;
+                    runoff_width = num(w)?;
+                }
+                ("walls", []) => walls = true,
+                ("grid", [n, d]) => {
+                    grid_slots = n.parse().map_err(|_| bad())?;
+                    grid_spacing = num(d)?;
+                }
+                ("point", [x, y]) => points.push((Point2::new(num(x)?, num(y)?), None)),
+                ("point", [x, y, w]) => points.push((Point2::new(num(x)?, num(y)?), Some(num(w)?))),
+                ("cone", [x, y]) => {
+                    let obb = Obb::new(Point2::new(num(x)?, num(y)?) * PIXELS_PER_METER,
+                        CONE_SIZE * PIXELS_PER_METER, CONE_SIZE * PIXELS_PER_METER, 0.);
+                    obstacles.push((CONE, obb));
+                    colliders.push(Collider { obb, material: Material::CONE });
+                }
+                ("barrier", [x, y, l, a]) => {
+                    let obb = Obb::new(Point2::new(num(x)?, num(y)?) * PIXELS_PER_METER,
+                        num(l)? * PIXELS_PER_METER, BARRIER_WIDTH * PIXELS_PER_METER, num(a)?.to_radians());
+                    obstacles.push((BARRIER, obb));
+                    colliders.push(Collider { obb, material: Material::TYRES });
+                }
+                _ => return Err(bad()),
+            }
+        }
+        if points.len() < 3 {
+            return Err("A track needs at least three points".to_owned())
+        }
+        // The centreline would stop dead at a point given twice in a row, the last point leading back to the first
+        for (i, &(p, _)) in points.iter().enumerate() {
+            if p == points[(i + 1) % points.len()].0 {
+                return Err(format!("A track can't have the same point twice in a row: {} {}", p.x, p.y))
+            }
+        }
+
+        let samples = sample_centreline(&points, width, kerb_radius);
+        let length = samples[samples.len() - 1].dist
+            + (samples[0].pos - samples[samples.len() - 1].pos).norm() / PIXELS_PER_METER;
+        // Also catches NaN from points all on top of each other
+        if !(length > 0.) {
+            return Err("A track can't have zero length".to_owned())
+        }
+        let walls: Vec<Vec<_>> = if walls {
+            let outside = |s: &Sample| (0.5 * s.width + kerb_width + runoff_width) * PIXELS_PER_METER * s.normal;
+            vec![
+                samples.iter().map(|s| s.pos - outside(s)).collect(),
+                samples.iter().map(|s| s.pos + outside(s)).collect(),
+            ]
+        } else {
+            Vec::new()
+        };
+        for wall in &walls {
+            for (i, &a) in wall.iter().enumerate() {
+                let b = wall[(i + 1) % wall.len()];
+                colliders.push(Collider {
+                    obb: Obb::segment(a, b, WALL_WIDTH * PIXELS_PER_METER),
+                    material: Material::CONCRETE,
+                });
+            }
+        }
+
+        Ok(Track {
+            name,
+            samples,
+            length,
+            kerb_width,
+            runoff,
+            runoff_width,
+            walls,
+            obstacles,
+            colliders,
+            grid_slots,
+            grid_spacing,
+            meshes: Vec::new(),
+        })
+    }
+    /// Length of the centreline in m
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+    /// Where a point in the world is relative to the track
+    pub fn project(&self, p: Point2) -> TrackPos {
+        let n = self.samples.len();
+        let mut best = (::std::f32::INFINITY, 0, 0.);
+        for i in 0..n {
+            let (a, b) = (self.samples[i].pos, self.samples[(i + 1) % n].pos);
+            let ab = b - a;
+            let t = ((p - a).dot(&ab) / ab.norm_squared()).max(0.).min(1.);
+            let d = (p - (a + t * ab)).norm_squared();
+            if d < best.0 {
+                best = (d, i, t);
+            }
+        }
+
+        let (_, i, t) = best;
+        let (a, b) = (&self.samples[i], &self.samples[(i + 1) % n]);
+        let seg_len = (b.pos - a.pos).norm() / PIXELS_PER_METER;
+        let closest = a.pos + t * (b.pos - a.pos);
+        let normal = (1. - t) * a.normal + t * b.normal;
+        TrackPos {
+            distance: a.dist + t * seg_len,
+            offset: (p - closest).dot(&normal) / PIXELS_PER_METER,
+            width: (1. - t) * a.width + t * b.width,
+        }
+    }
+    /// Distance from the start line along the track in m of the point closest to `p`
+    pub fn distance(&self, p: Point2) -> f32 {
+        self.project(p).distance
+    }
+    /// Whether a point is on the road or its kerbs
+    pub fn is_on_track(&self, p: Point2) -> bool {
+        let tp = self.project(p);
+        tp.offset.abs() <= 0.5 * tp.width + self.kerb_width
+    }
+    /// The point on the centreline `distance` m from the start line and the direction of the track there
+    pub fn point_at(&self, distance: f32) -> (Point2, Vector2) {
+        let d = ((distance % self.length) + self.length) % self.length;
+        let n = self.samples.len();
+        // The last sample before the distance
+        // A distance that isn't a number ends up at the last sample
+        let i = match self.samples.binary_search_by(|s| s.dist.partial_cmp(&d).unwrap_or(Ordering::Less)) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        let (a, b) = (&self.samples[i], &self.samples[(i + 1) % n]);
+        let seg_len = (b.pos - a.pos).norm() / PIXELS_PER_METER;
+        let t = (d - a.dist) / seg_len;
+        let dir = Vector2::new(a.normal.y, -a.normal.x);
+        (a.pos + t * (b.pos - a.pos), dir)
+    }
+    /// Number of slots on the start grid
+    pub fn grid_slots(&self) -> u32 {
+        self.grid_slots
+    }
+    /// Where a car on a slot of the start grid starts, with the slots staggered left and right
+    pub fn grid(&self, slot: u32) -> Object {
+        let (pos, dir) = self.point_at(-self.grid_spacing * (slot + 1) as f32);
+        let right = Vector2::new(-dir.y, dir.x);
+        let side = if slot % 2 == 0 { -1. } else { 1. };
+        let width = self.project(pos).width;
+        Object {
+            pos: pos + side * 0.25 * width * PIXELS_PER_METER * right,
+            rot: angle_from_vec(&dir),
+        }
+    }
+    /// The surface at a point in the world
+    pub fn surface(&self, p: Point2) -> Surface {
+        let tp = self.project(p);
+        let edge = 0.5 * tp.width + self.kerb_width;
+        let offset = tp.offset.abs();
+        if offset <= edge {
+            Surface::Asphalt
+        } else if offset <= edge + self.runoff_width {
+            self.runoff
+        } else {
+            Surface::Grass
+        }
+    }
+    /// Triangles between two offsets from the centreline in m along the samples `keep` says yes to
+    fn strip<F, K>(&self, offsets: F, keep: K) -> Vec<Point2>
+        where F: Fn(&Sample) -> (f32, f32), K: Fn(usize) -> bool {
+        let n = self.samples.len();
+        let mut triangles = Vec::new();
+        for i in (0..n).filter(|&i| keep(i)) {
+            let (a, b) = (&self.samples[i], &self.samples[(i + 1) % n]);
+            let (a_from, a_to) = offsets(a);
+            let (b_from, b_to) = offsets(b);
+            let p = |s: &Sample, o: f32| s.pos + o * PIXELS_PER_METER * s.normal;
+            let (a1, a2, b1, b2) = (p(a, a_from), p(a, a_to), p(b, b_from), p(b, b_to));
+            triangles.extend_from_slice(&[a1, a2, b2, a1, b2, b1]);
+        }
+        triangles
+    }
+    /// Makes the meshes of the run-off, road, kerbs, start line, grid and walls
+    fn build_meshes(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let colour = |s: Surface| {
+            let [r, g, b] = s.colour();
+            Color::from_rgb(r, g, b)
+        };
+        let kerb = self.kerb_width;
+        let outside = self.kerb_width + self.runoff_width;
+        let all = |_: usize| true;
+
+        let mut pieces = vec![
+            (colour(self.runoff), self.strip(|s| (-0.5 * s.width - outside, 0.5 * s.width + outside), all)),
+            (colour(Surface::Asphalt), self.strip(|s| (-0.5 * s.width, 0.5 * s.width), all)),
+        ];
+        // The kerbs are striped with every sample
+        for &(colour, stripe) in &[(KERB_RED, 0), (KERB_WHITE, 1)] {
+            let keep = |i: usize| i % 2 == stripe && self.samples[i].kerb;
+            let mut triangles = self.strip(|s| (-0.5 * s.width - kerb, -0.5 * s.width), &keep);
+            triangles.extend(self.strip(|s| (0.5 * s.width, 0.5 * s.width + kerb), &keep));
+            pieces.push((colour, triangles));
+        }
+
+        // The start line and a mark in front of each slot on the grid
+        let (start, dir) = self.point_at(0.);
+        let width = self.project(start).width;
+        let mut lines = line_across(start, dir, -0.5 * width, 0.5 * width);
+        for slot in 0..self.grid_slots {
+            let car = self.grid(slot);
+            let dir = Vector2::new(car.rot.cos(), car.rot.sin());
+            lines.extend(line_across(car.pos + 2.5 * PIXELS_PER_METER * dir, dir, -1.5, 1.5));
+        }
+        pieces.push((graphics::WHITE, lines));
+
+        for (colour, triangles) in pieces {
+            if !triangles.is_empty() {
+                self.meshes.push((colour, Mesh::from_triangles(ctx, &triangles)?));
+            }
+        }
+        for wall in &self.walls {
+            let mut points = wall.clone();
+            points.push(wall[0]);
+            self.meshes.push((WALL, Mesh::new_line(ctx, &points, WALL_WIDTH * PIXELS_PER_METER)?));
+        }
+        for &(colour, ref obb) in &self.obstacles {
+            let [a, b, c, d] = obb.corners();
+            self.meshes.push((colour, Mesh::from_triangles(ctx, &[a, b, c, a, c, d])?));
+        }
+        Ok(())
+    }
+    /// Draws the track
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        for &(colour, ref mesh) in &self.meshes {
+            graphics::set_color(ctx, colour)?;
+            mesh.draw(ctx, Point2::new(0., 0.), 0.)?;
+        }
+        graphics::set_color(ctx, graphics::WHITE)
+    }
+}
+
+/// Triangles of a half metre thick line across the direction `dir` from `from` to `to` m to the right of `pos`
+fn line_across(pos: Point2, dir: Vector2, from: f32, to: f32) -> Vec<Point2> {
+    let right = Vector2::new(-dir.y, dir.x) * PIXELS_PER_METER;
+    let back = -0.5 * dir * PIXELS_PER_METER;
+    let (a, b) = (pos + from * right, pos + to * right);
+    vec![a, b, b + back, a, b + back, a + back]
+}
+
+/// Samples the closed Catmull-Rom spline through the points, turning metres into pixels
+fn sample_centreline(points: &[(Point2, Option<f32>)], width: f32, kerb_radius: f32) -> Vec<Sample> {
+    let n = points.len();
+    let p = |i: usize| points[i % n].0.coords * PIXELS_PER_METER;
+    let w = |i: usize| points[i % n].1.unwrap_or(width);
+
+    let mut samples: Vec<Sample> = Vec::with_capacity(n * SAMPLES_PER_SPAN);
+    for i in 0..n {
+        let (p0, p1, p2, p3) = (p(i + n - 1), p(i), p(i + 1), p(i + 2));
+        for k in 0..SAMPLES_PER_SPAN {
+            let t = k as f32 / SAMPLES_PER_SPAN as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let pos = 0.5 * (2. * p1 + (p2 - p0) * t + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2 + (3. * p1 - p0 - 3. * p2 + p3) * t3);
+            let pos = Point2::origin() + pos;
+            let dist = match samples.last() {
+                Some(last) => last.dist + (pos - last.pos).norm() / PIXELS_PER_METER,
+                None => 0.,
+            };
+            samples.push(Sample {
+                pos,
+                normal: Vector2::new(0., 0.),
+                width: (1. - t) * w(i) + t * w(i + 1),
+                dist,
+                kerb: false,
+            });
+        }
+    }
+
+    // The normals and kerbs need the neighbouring samples
+    let m = samples.len();
+    for i in 0..m {
+        let (prev, next) = (samples[(i + m - 1) % m].pos, samples[(i + 1) % m].pos);
+        let dir = (next - prev).normalize();
+        samples[i].normal = Vector2::new(-dir.y, dir.x);
+
+        let (d1, d2) = (samples[i].pos - prev, next - samples[i].pos);
+        let turn = (d1.x * d2.y - d1.y * d2.x).atan2(d1.dot(&d2)).abs();
+        let radius = 0.5 * (d1.norm() + d2.norm()) / PIXELS_PER_METER / turn;
+        samples[i].kerb = radius < kerb_radius;
+    }
+    samples
+}